@@ -694,7 +694,7 @@ async fn gather_file_paths_to_remove(
 mod tests {
 	use super::*;
 
-	use sd_core_indexer_rules::{IndexerRule, RulePerKind};
+	use sd_core_indexer_rules::{seed::NO_SYSTEM_FILES, IndexerRule, RulePerKind};
 	use sd_core_prisma_helpers::FilePathPubId;
 	use sd_task_system::{TaskOutput, TaskStatus, TaskSystem};
 
@@ -1173,4 +1173,49 @@ mod tests {
 		)
 		.await;
 	}
+
+	#[tokio::test]
+	#[traced_test]
+	async fn skips_location_metadata_files() {
+		let root = tempdir().unwrap();
+		let root_path = root.path();
+
+		for file_name in [
+			".spacedrive",
+			".spacedrive.tmp",
+			".spacedrive.bak",
+			".spacedrive.corrupt-1700000000",
+			"notes.txt",
+		] {
+			fs::File::create(root_path.join(file_name)).await.unwrap();
+		}
+
+		let metadata = FilePathMetadata {
+			inode: 0,
+			size_in_bytes: 0,
+			created_at: Utc::now(),
+			modified_at: Utc::now(),
+			hidden: false,
+		};
+
+		let expected = HashSet::from([WalkedEntry {
+			pub_id: FilePathPubId::new(),
+			maybe_object_id: None,
+			iso_file_path: IsolatedFilePathData::new(
+				0,
+				root_path,
+				root_path.join("notes.txt"),
+				false,
+			)
+			.unwrap(),
+			metadata,
+		}]);
+
+		run_test(
+			root_path,
+			IndexerRuler::new(vec![IndexerRule::from(&*NO_SYSTEM_FILES)]),
+			expected,
+		)
+		.await;
+	}
 }
@@ -217,7 +217,9 @@ pub static NO_SYSTEM_FILES: LazyLock<SystemIndexerRule> = LazyLock::new(|| {
 		RulePerKind::new_reject_files_by_globs_str(
 			[
 				vec![
+					// Location metadata file, and its temporary, backup and corrupted copies
 					"**/.spacedrive",
+					"**/.spacedrive.*",
 				],
 				// Globset, even on Windows, requires the use of / as a separator
 				// https://github.com/github/gitignore/blob/main/Global/Windows.gitignore
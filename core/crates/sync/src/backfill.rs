@@ -1,26 +1,746 @@
 use sd_prisma::{
 	prisma::{
-		crdt_operation, device, exif_data, file_path, label, label_on_object, location, object,
-		tag, tag_on_object, volume, PrismaClient, SortOrder,
+		album, backfill_checkpoint, crdt_operation, device, exif_data, file_path, indexer_rule,
+		indexer_rules_in_location, label, label_on_object, location, object, object_in_album,
+		preference, saved_search, tag, tag_on_object, volume, PrismaClient, SortOrder,
 	},
 	prisma_sync,
 };
-use sd_sync::{option_sync_entry, sync_entry, OperationFactory};
-use sd_utils::chain_optional_iter;
+use sd_sync::{
+	option_sync_entry,
+	pagination::{self, PaginationError},
+	sync_entry, CRDTOperation, ModelId, OperationFactory, OperationKind,
+};
+use sd_utils::{chain_optional_iter, from_bytes_to_uuid};
 
-use std::future::Future;
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+	future::Future,
+	iter, mem,
+	pin::pin,
+	sync::atomic::{AtomicI64, Ordering},
+	time::Duration,
+};
 
+use async_stream::try_stream;
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::{
+	stream::{self, BoxStream, FuturesUnordered, Stream},
+	StreamExt, TryFutureExt, TryStreamExt,
+};
 use futures_concurrency::future::TryJoin;
-use tokio::time::Instant;
-use tracing::{debug, instrument};
+use prisma_client_rust::{and, not, or};
+use tokio::time::{sleep, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+use uuid::Uuid;
+
+use super::{crdt_op_unchecked_db, DevicePubId, Error, SyncManager};
+
+/// Syncable entities that get their operations generated by a backfill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BackfillEntity {
+	Device,
+	Volume,
+	Tag,
+	Location,
+	Object,
+	Label,
+	Album,
+	SavedSearch,
+	Preference,
+	ExifData,
+	FilePath,
+	TagOnObject,
+	LabelOnObject,
+	ObjectInAlbum,
+	IndexerRule,
+	IndexerRulesInLocation,
+}
+
+impl BackfillEntity {
+	/// Every entity, in declaration order.
+	///
+	/// Backfills iterate this registry, so every syncable model of the schema must be listed
+	/// here, which is enforced by the `every_synced_model_is_backfilled` test.
+	pub const ALL: [Self; 16] = [
+		Self::Device,
+		Self::Volume,
+		Self::Tag,
+		Self::Location,
+		Self::Object,
+		Self::Label,
+		Self::Album,
+		Self::SavedSearch,
+		Self::Preference,
+		Self::ExifData,
+		Self::FilePath,
+		Self::TagOnObject,
+		Self::LabelOnObject,
+		Self::ObjectInAlbum,
+		Self::IndexerRule,
+		Self::IndexerRulesInLocation,
+	];
+
+	/// Entities referenced by the operations of this one, which must be backfilled before it.
+	#[must_use]
+	pub const fn dependencies(self) -> &'static [Self] {
+		match self {
+			Self::Device
+			| Self::Tag
+			| Self::Label
+			| Self::Album
+			| Self::SavedSearch
+			| Self::Preference
+			| Self::IndexerRule => &[],
+			Self::Volume | Self::Location | Self::Object => &[Self::Device],
+			Self::ExifData => &[Self::Device, Self::Object],
+			Self::FilePath => &[Self::Device, Self::Location, Self::Object],
+			Self::TagOnObject => &[Self::Device, Self::Tag, Self::Object],
+			Self::LabelOnObject => &[Self::Device, Self::Label, Self::Object],
+			Self::ObjectInAlbum => &[Self::Album, Self::Object],
+			Self::IndexerRulesInLocation => &[Self::Location, Self::IndexerRule],
+		}
+	}
+
+	/// Whether only the rows of the devices in [`BackfillConfig::devices`] are backfilled for
+	/// this entity.
+	#[must_use]
+	pub const fn is_device_scoped(self) -> bool {
+		matches!(
+			self,
+			Self::Volume
+				| Self::Location
+				| Self::Object
+				| Self::ExifData
+				| Self::FilePath
+				| Self::TagOnObject
+				| Self::LabelOnObject
+				| Self::IndexerRulesInLocation
+		)
+	}
+
+	/// Whether the rows of this entity have a modification date, so they can be filtered with
+	/// [`BackfillConfig::since`].
+	#[must_use]
+	pub const fn has_modification_date(self) -> bool {
+		matches!(
+			self,
+			Self::Tag
+				| Self::Volume
+				| Self::Label
+				| Self::Album
+				| Self::SavedSearch
+				| Self::IndexerRule
+				| Self::FilePath
+		)
+	}
+
+	/// Sync model id of the entity, stored in the `model` column of its operations.
+	#[must_use]
+	pub const fn model_id(self) -> ModelId {
+		match self {
+			Self::Device => prisma_sync::device::MODEL_ID,
+			Self::Volume => prisma_sync::volume::MODEL_ID,
+			Self::Tag => prisma_sync::tag::MODEL_ID,
+			Self::Location => prisma_sync::location::MODEL_ID,
+			Self::Object => prisma_sync::object::MODEL_ID,
+			Self::Label => prisma_sync::label::MODEL_ID,
+			Self::Album => prisma_sync::album::MODEL_ID,
+			Self::SavedSearch => prisma_sync::saved_search::MODEL_ID,
+			Self::Preference => prisma_sync::preference::MODEL_ID,
+			Self::ExifData => prisma_sync::exif_data::MODEL_ID,
+			Self::FilePath => prisma_sync::file_path::MODEL_ID,
+			Self::TagOnObject => prisma_sync::tag_on_object::MODEL_ID,
+			Self::LabelOnObject => prisma_sync::label_on_object::MODEL_ID,
+			Self::ObjectInAlbum => prisma_sync::object_in_album::MODEL_ID,
+			Self::IndexerRule => prisma_sync::indexer_rule::MODEL_ID,
+			Self::IndexerRulesInLocation => prisma_sync::indexer_rules_in_location::MODEL_ID,
+		}
+	}
+
+	/// Prisma model name of the entity, also used to key its backfill checkpoint.
+	#[must_use]
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Device => device::NAME,
+			Self::Volume => volume::NAME,
+			Self::Tag => tag::NAME,
+			Self::Location => location::NAME,
+			Self::Object => object::NAME,
+			Self::Label => label::NAME,
+			Self::Album => album::NAME,
+			Self::SavedSearch => saved_search::NAME,
+			Self::Preference => preference::NAME,
+			Self::ExifData => exif_data::NAME,
+			Self::FilePath => file_path::NAME,
+			Self::TagOnObject => tag_on_object::NAME,
+			Self::LabelOnObject => label_on_object::NAME,
+			Self::ObjectInAlbum => object_in_album::NAME,
+			Self::IndexerRule => indexer_rule::NAME,
+			Self::IndexerRulesInLocation => indexer_rules_in_location::NAME,
+		}
+	}
+}
+
+/// Order in which entities are backfilled, derived from [`BackfillEntity::dependencies`].
+///
+/// Entities are split in stages, each one only depending on entities from previous stages,
+/// so the entities of a stage can be backfilled concurrently. Backfills don't wait for a whole
+/// stage though, starting each entity as soon as its dependencies are backfilled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackfillPlan {
+	stages: Vec<Vec<BackfillEntity>>,
+}
+
+impl BackfillPlan {
+	/// Schedules `entities`, ignoring the dependencies left out of them, which are assumed to be
+	/// backfilled already.
+	///
+	/// # Panics
+	///
+	/// Panics if [`BackfillEntity::dependencies`] has a cycle.
+	#[must_use]
+	pub fn new(entities: impl IntoIterator<Item = BackfillEntity>) -> Self {
+		let mut pending = entities.into_iter().collect::<BTreeSet<_>>();
+		let mut stages = vec![];
+
+		while !pending.is_empty() {
+			let stage = pending
+				.iter()
+				.copied()
+				.filter(|entity| {
+					entity
+						.dependencies()
+						.iter()
+						.all(|dependency| !pending.contains(dependency))
+				})
+				.collect::<Vec<_>>();
+
+			assert!(
+				!stage.is_empty(),
+				"cyclic dependencies between backfill entities: {pending:?}"
+			);
+
+			for entity in &stage {
+				pending.remove(entity);
+			}
+
+			stages.push(stage);
+		}
+
+		Self { stages }
+	}
+
+	#[must_use]
+	pub fn stages(&self) -> &[Vec<BackfillEntity>] {
+		&self.stages
+	}
+
+	/// All the scheduled entities, in an order respecting their dependencies.
+	pub fn entities(&self) -> impl Iterator<Item = BackfillEntity> + '_ {
+		self.stages.iter().flatten().copied()
+	}
+}
+
+impl Default for BackfillPlan {
+	fn default() -> Self {
+		Self::new(BackfillEntity::ALL)
+	}
+}
+
+/// Progress of a running backfill, reported by [`backfill_operations_with_progress`].
+#[derive(Debug, Clone, Copy)]
+pub struct BackfillProgress {
+	/// The entity being backfilled.
+	pub entity: BackfillEntity,
+	/// Rows of this entity processed so far.
+	pub processed: u64,
+	/// Best-effort total of rows for this entity, counted before the backfill started.
+	pub total: u64,
+}
+
+/// Summary of a finished backfill.
+#[derive(Debug, Default)]
+pub struct BackfillReport {
+	/// How many CRDT operations were generated for each entity.
+	pub operations: BTreeMap<BackfillEntity, u64>,
+	/// Entities that failed to backfill, only filled in [`BackfillConfig::best_effort`] mode.
+	pub failures: Vec<(BackfillEntity, Error)>,
+	/// How long the backfill took, not counting the time spent waiting for the sync lock.
+	pub elapsed: Duration,
+}
+
+impl BackfillReport {
+	/// Total of CRDT operations generated for all entities.
+	#[must_use]
+	pub fn total_operations(&self) -> u64 {
+		self.operations.values().sum()
+	}
+}
+
+/// Rough size of a backfill, computed by [`estimate_backfill`] without running it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackfillEstimate {
+	/// How many rows would be backfilled for each entity.
+	pub rows: BTreeMap<BackfillEntity, u64>,
+	/// Rows of all entities.
+	pub total_rows: u64,
+	/// Time the backfill would take at [`BackfillConfig::estimated_rows_per_second`].
+	pub duration: Duration,
+}
+
+/// Entity whose rows don't match its backfilled operations, found by [`verify_backfill`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Discrepancy {
+	pub model: BackfillEntity,
+	/// Rows of the entity that should have been backfilled.
+	pub source_rows: u64,
+	/// Create operations of the entity generated by the device.
+	pub operation_rows: u64,
+}
+
+/// How often a chunked backfill commits, see [`backfill_operations_chunked`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackfillChunking {
+	/// Commit once per entity.
+	Entity,
+	/// Commit every given number of pages, and once more at the end of each entity. The pages
+	/// are fetched outside of the transactions, so the database is only locked while writing.
+	Pages(usize),
+}
+
+/// Devices whose rows are backfilled, see [`BackfillConfig::devices`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BackfillDevices {
+	/// Only the backfilling device.
+	#[default]
+	Local,
+	/// The backfilling device and these other ones, failing with [`Error::DeviceNotFound`] if
+	/// any of them is missing.
+	Including(Vec<DevicePubId>),
+	/// Every device of the library.
+	All,
+}
+
+/// Tunables for [`backfill_operations_with_progress`].
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+	/// How many rows are fetched and turned into operations at once, bounding memory usage.
+	pub page_size: usize,
+	/// Backfill each entity in its own transaction, collecting failures in
+	/// [`BackfillReport::failures`] instead of rolling back every entity on the first error.
+	///
+	/// Entities referencing a failed one are still backfilled, so their operations may point
+	/// to records that other devices won't receive until a later backfill succeeds.
+	pub best_effort: bool,
+	/// Regenerate the operations even if the device already has some, instead of failing with
+	/// [`Error::AlreadyBackfilled`].
+	pub force: bool,
+	/// How many times a page is attempted when it fails with a transient error, like SQLite
+	/// being locked by a concurrent writer, before giving up with
+	/// [`Error::BackfillRetryExhausted`].
+	pub retry_attempts: u32,
+	/// Delay before the first retry of a page, doubled on each following retry.
+	pub retry_delay: Duration,
+	/// Also backfill objects without a device, like the ones created before devices were tracked,
+	/// which would otherwise never sync. Their operations carry no device.
+	///
+	/// As these objects aren't owned by any device, every device backfilling with this flag
+	/// generates operations for them.
+	pub include_deviceless_objects: bool,
+	/// Keep the existing operations of the device and only generate operations for the records
+	/// that have none yet, from any device, instead of wiping them and starting over. This keeps
+	/// the edits made after sync was enabled.
+	pub deduplicate: bool,
+	/// Skip relation creates whose record was already backfilled by this run, logging a warning
+	/// with the repeated key, instead of generating duplicate operations for it. Relations are
+	/// unique in a healthy database, but a corrupted one may repeat them, which would confuse the
+	/// merge on the receiving devices.
+	///
+	/// Keeps the key of every relation backfilled, so it's off by default.
+	pub deduplicate_relations: bool,
+	/// How long a backfill transaction can run before being rolled back, releasing the sync lock.
+	/// Backfilling large libraries on slow devices can take a while, so the default is generous,
+	/// but lowering it makes a stuck database fail fast instead of hanging.
+	pub transaction_timeout: Duration,
+	/// Size in bytes that the serialized operations of a page shouldn't exceed. Pages going over
+	/// it halve the rows fetched by the next page, and pages well under it double them, up to
+	/// [`BackfillConfig::page_size`]. Keeps memory usage bounded on tables with large rows.
+	pub page_byte_budget: Option<usize>,
+	/// How many pages of the largest tables, objects and file paths, are fetched and built
+	/// concurrently. Their operations are still written one page at a time.
+	pub parallel_pages: usize,
+	/// Fail with an error on broken references found while backfilling, like a volume whose
+	/// device is missing, instead of only logging a warning and generating operations without
+	/// the reference.
+	pub strict: bool,
+	/// Commit the backfill in chunks, each in its own transaction, instead of holding the
+	/// database write lock for the whole backfill. Failing fast like the default mode, but an
+	/// interrupted backfill keeps its committed chunks, resuming from their checkpoints.
+	///
+	/// Ignored with [`BackfillConfig::best_effort`], which already commits each entity on its own.
+	pub chunking: Option<BackfillChunking>,
+	/// Rows backfilled per second assumed by [`estimate_backfill_with_config`]. The default is
+	/// a conservative guess for a mobile device, tune it with measurements from real backfills.
+	pub estimated_rows_per_second: u64,
+	/// Only backfill the rows modified at or after this date, like the ones touched by a
+	/// migration, keeping the existing operations of the device instead of regenerating its whole
+	/// history.
+	///
+	/// Only volumes, tags, labels, albums, saved searches, indexer rules and file paths have a
+	/// modification date, see [`BackfillEntity::has_modification_date`]. Every row of the other
+	/// entities is still fetched, but deduplicated like with [`BackfillConfig::deduplicate`], so
+	/// only the ones without operations yet get some.
+	pub since: Option<DateTime<Utc>>,
+	/// Backfill the entities one after the other, in the order of the [`BackfillPlan`], fetching
+	/// a single page at a time, ignoring [`BackfillConfig::parallel_pages`]. Two backfills of the
+	/// same database then write the same operations in the same order, apart from their
+	/// timestamps, making them easy to diff in tests.
+	pub deterministic: bool,
+	/// Devices whose volumes, locations, objects, file paths and the like are backfilled, along
+	/// with their device rows. Only the backfilling device by default, but a library that
+	/// aggregated the data of other devices before sync was enabled needs theirs backfilled too,
+	/// or it never syncs.
+	///
+	/// The operations are still generated by the backfilling device.
+	pub devices: BackfillDevices,
+}
+
+impl Default for BackfillConfig {
+	fn default() -> Self {
+		Self {
+			page_size: 1000,
+			best_effort: false,
+			force: false,
+			retry_attempts: 5,
+			retry_delay: Duration::from_millis(100),
+			include_deviceless_objects: false,
+			deduplicate: false,
+			deduplicate_relations: false,
+			transaction_timeout: Duration::from_secs(30 * 60),
+			page_byte_budget: None,
+			parallel_pages: 1,
+			strict: false,
+			chunking: None,
+			estimated_rows_per_second: 2000,
+			since: None,
+			deterministic: false,
+			devices: BackfillDevices::Local,
+		}
+	}
+}
+
+impl BackfillConfig {
+	fn transaction_timeout_ms(&self) -> u64 {
+		u64::try_from(self.transaction_timeout.as_millis()).unwrap_or(u64::MAX)
+	}
+
+	/// Whether the existing operations of the device are kept, instead of being regenerated.
+	const fn keeps_operations(&self) -> bool {
+		self.deduplicate || self.since.is_some()
+	}
 
-use super::{crdt_op_unchecked_db, Error, SyncManager};
+	/// Whether the operations of `entity` are deduplicated against the existing ones, which is
+	/// forced for entities that can't be filtered by [`BackfillConfig::since`].
+	const fn deduplicates(&self, entity: BackfillEntity) -> bool {
+		self.deduplicate || (self.since.is_some() && !entity.has_modification_date())
+	}
+
+	fn since(&self) -> Option<DateTime<FixedOffset>> {
+		self.since.map(Into::into)
+	}
+
+	const fn parallel_pages(&self) -> usize {
+		if self.deterministic {
+			1
+		} else {
+			self.parallel_pages
+		}
+	}
+}
+
+/// State shared by every entity backfill of a single [`backfill_operations`] run.
+#[derive(Clone, Copy)]
+struct BackfillContext<'a> {
+	db: &'a PrismaClient,
+	sync: &'a SyncManager,
+	local_device: &'a device::Data,
+	device_id: device::id::Type,
+	/// Devices whose rows are backfilled, the local one first
+	device_ids: &'a [device::id::Type],
+	config: &'a BackfillConfig,
+	totals: &'a HashMap<BackfillEntity, u64>,
+	checkpoints: &'a HashMap<String, backfill_checkpoint::Data>,
+	on_progress: &'a (dyn Fn(BackfillProgress) + Send + Sync),
+	cancellation_token: &'a CancellationToken,
+	/// Build the operations without writing them, nor any checkpoint
+	dry_run: bool,
+	/// Store a checkpoint after each page, for an interrupted run to resume from. Only full
+	/// backfills clear them, so the other runs must not leave any behind
+	save_checkpoints: bool,
+}
+
+impl BackfillContext<'_> {
+	fn page_size(&self) -> i64 {
+		i64::try_from(self.config.page_size.max(1)).unwrap_or(i64::MAX)
+	}
+
+	/// Adapts how many rows the next page fetches to keep the size of its operations within
+	/// [`BackfillConfig::page_byte_budget`], if any.
+	fn next_page_size(&self, take: i64, operations: &[CRDTOperation]) -> Result<i64, Error> {
+		let Some(budget) = self.config.page_byte_budget else {
+			return Ok(take);
+		};
+
+		let bytes = rmp_serde::to_vec(operations)?.len();
+
+		let next_take = if bytes > budget {
+			(take / 2).max(1)
+		} else if bytes < budget / 4 {
+			take.saturating_mul(2).min(self.page_size())
+		} else {
+			take
+		};
+
+		if next_take != take {
+			debug!(bytes, take, next_take, "adapting backfill page size");
+		}
+
+		Ok(next_take)
+	}
+
+	fn ensure_not_cancelled(&self) -> Result<(), Error> {
+		if self.cancellation_token.is_cancelled() {
+			Err(Error::Cancelled)
+		} else {
+			Ok(())
+		}
+	}
+
+	fn checkpoint(&self, entity: BackfillEntity) -> Option<&backfill_checkpoint::Data> {
+		self.checkpoints.get(entity.name())
+	}
+
+	/// Stores the cursor of the last backfilled page of `entity`. It's written with the same
+	/// client as the page's operations, so a checkpoint is only ever committed together with
+	/// the operations it accounts for.
+	async fn save_checkpoint(
+		&self,
+		entity: BackfillEntity,
+		cursor: i32,
+		item_cursor: Option<i32>,
+	) -> Result<(), Error> {
+		if self.dry_run || !self.save_checkpoints {
+			return Ok(());
+		}
+
+		self.db
+			.backfill_checkpoint()
+			.upsert(
+				backfill_checkpoint::device_pub_id_entity(
+					self.sync.device_pub_id.to_db(),
+					entity.name().to_string(),
+				),
+				backfill_checkpoint::create(
+					self.sync.device_pub_id.to_db(),
+					entity.name().to_string(),
+					cursor,
+					vec![backfill_checkpoint::item_cursor::set(item_cursor)],
+				),
+				vec![
+					backfill_checkpoint::cursor::set(cursor),
+					backfill_checkpoint::item_cursor::set(item_cursor),
+				],
+			)
+			.exec()
+			.await?;
+
+		Ok(())
+	}
+
+	/// Runs `f` until it succeeds or fails with a non transient error, retrying with an
+	/// exponential backoff up to [`BackfillConfig::retry_attempts`] times.
+	async fn with_retry<T, Fut>(&self, f: impl Fn() -> Fut + Send) -> Result<T, Error>
+	where
+		T: Send,
+		Fut: Future<Output = Result<T, Error>> + Send,
+	{
+		let mut attempts = 0;
+		let mut delay = self.config.retry_delay;
+
+		loop {
+			attempts += 1;
+
+			match f().await {
+				Err(e) if is_transient(&e) => {
+					if attempts >= self.config.retry_attempts {
+						return Err(Error::BackfillRetryExhausted {
+							attempts,
+							source: Box::new(e),
+						});
+					}
+
+					warn!(
+						attempts,
+						?delay,
+						?e,
+						"Transient error during backfill, retrying;"
+					);
+
+					sleep(delay).await;
+					delay = delay.saturating_mul(2);
+				}
+				res => return res,
+			}
+		}
+	}
+
+	/// Drops the operations of records that already have operations in the sync log, from any
+	/// device, as they are already known to sync.
+	async fn without_synced_records(
+		&self,
+		operations: Vec<CRDTOperation>,
+	) -> Result<Vec<CRDTOperation>, Error> {
+		let Some(model_id) = operations.first().map(|op| op.model_id) else {
+			return Ok(operations);
+		};
+
+		let record_ids = operations
+			.iter()
+			.map(|op| rmp_serde::to_vec(&op.record_id))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let synced = self
+			.db
+			.crdt_operation()
+			.find_many(vec![
+				crdt_operation::model::equals(i32::from(model_id)),
+				crdt_operation::record_id::in_vec(record_ids.clone()),
+			])
+			.select(crdt_operation::select!({ record_id }))
+			.exec()
+			.await?
+			.into_iter()
+			.map(|op| op.record_id)
+			.collect::<HashSet<_>>();
+
+		Ok(operations
+			.into_iter()
+			.zip(record_ids)
+			.filter_map(|(op, record_id)| (!synced.contains(&record_id)).then_some(op))
+			.collect())
+	}
+
+	/// Inserts the operations of a page, or only counts them on a dry run.
+	async fn write_operations(&self, operations: &[CRDTOperation]) -> Result<u64, Error> {
+		if self.dry_run {
+			return Ok(operations.len() as u64);
+		}
+
+		let count = self
+			.db
+			.crdt_operation()
+			.create_many(
+				operations
+					.iter()
+					.map(crdt_op_unchecked_db)
+					.collect::<Result<Vec<_>, _>>()?,
+			)
+			.exec()
+			.await?;
+
+		Ok(u64::try_from(count).unwrap_or_default())
+	}
+
+	/// Called when `entity` has no rows to backfill, telling apart a device that has none from
+	/// a device row deleted since the backfill started, which fails with [`Error::DeviceNotFound`].
+	async fn check_no_rows(&self, entity: BackfillEntity) -> Result<(), Error> {
+		if !entity.is_device_scoped() {
+			debug!(?entity, "no rows to backfill");
+			return Ok(());
+		}
+
+		let device_exists = self
+			.db
+			.device()
+			.count(vec![device::id::equals(self.device_id)])
+			.exec()
+			.await? > 0;
+
+		if device_exists {
+			debug!(?entity, "no rows to backfill for the device");
+			Ok(())
+		} else {
+			debug!(?entity, "device gone while backfilling");
+			Err(Error::DeviceNotFound(self.sync.device_pub_id.clone()))
+		}
+	}
+
+	fn report_progress(&self, entity: BackfillEntity, processed: u64) {
+		(self.on_progress)(BackfillProgress {
+			entity,
+			processed,
+			total: self.totals.get(&entity).copied().unwrap_or_default(),
+		});
+	}
+}
 
 /// Takes all the syncable data in the database and generates [`CRDTOperations`] for it.
 /// This is a requirement before the library can sync.
-pub async fn backfill_operations(sync: &SyncManager) -> Result<(), Error> {
+///
+/// After each page a checkpoint with the page's cursor is stored in the `backfill_checkpoint`
+/// table, scoped by device. If checkpoints from a previous run are found, the existing operations
+/// are kept and each entity resumes after its stored cursor instead of starting over. The
+/// checkpoints are cleared once the backfill completes.
+///
+/// The backfill runs in a transaction rolled back if it takes longer than
+/// [`BackfillConfig::transaction_timeout`], 30 minutes by default, failing with a database error.
+///
+/// Fails with [`Error::AlreadyBackfilled`] if the device already has operations and there is no
+/// interrupted backfill to resume, as regenerating them would wipe its sync log. Set
+/// [`BackfillConfig::force`] to regenerate them anyway.
+///
+/// Checkpoints are written with the same client as the operations they account for, so here,
+/// as the whole backfill runs in a single transaction, an interrupted run rolls them back
+/// together with its operations. With [`BackfillConfig::best_effort`] each entity commits on its
+/// own, so a retry skips the entities that completed.
+pub async fn backfill_operations(sync: &SyncManager) -> Result<BackfillReport, Error> {
+	backfill_operations_with_progress(sync, &BackfillConfig::default(), |_| {}).await
+}
+
+/// Same as [`backfill_operations`], but with a custom [`BackfillConfig`] and calling `on_progress`
+/// after each page of each entity is written, and once more when the entity completes.
+///
+/// The callback runs while the backfill transaction is open, so it must be cheap, like
+/// sending the progress through a channel to be handled somewhere else.
+pub async fn backfill_operations_with_progress(
+	sync: &SyncManager,
+	config: &BackfillConfig,
+	on_progress: impl Fn(BackfillProgress) + Send + Sync,
+) -> Result<BackfillReport, Error> {
+	backfill_operations_cancellable(sync, config, on_progress, &CancellationToken::new()).await
+}
+
+/// Same as [`backfill_operations_with_progress`], but stops with [`Error::Cancelled`] as soon as
+/// `cancellation_token` is cancelled, releasing the sync lock.
+///
+/// The token is checked before each page of each entity and between dependent stages of
+/// entities. Cancelling rolls back the enclosing transaction, so no operations generated by
+/// this run are left behind, except for the entities, or chunks, already committed in
+/// [`BackfillConfig::best_effort`] or [`BackfillConfig::chunking`] modes.
+pub async fn backfill_operations_cancellable(
+	sync: &SyncManager,
+	config: &BackfillConfig,
+	on_progress: impl Fn(BackfillProgress) + Send + Sync,
+	cancellation_token: &CancellationToken,
+) -> Result<BackfillReport, Error> {
 	let _lock_guard = sync.sync_lock.lock().await;
 
+	if cancellation_token.is_cancelled() {
+		return Err(Error::Cancelled);
+	}
+
 	let db = &sync.db;
 
 	let local_device = db
@@ -30,195 +750,1418 @@ pub async fn backfill_operations(sync: &SyncManager) -> Result<(), Error> {
 		.await?
 		.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
 
-	let local_device_id = local_device.id;
+	let checkpoints = db
+		.backfill_checkpoint()
+		.find_many(vec![backfill_checkpoint::device_pub_id::equals(
+			sync.device_pub_id.to_db(),
+		)])
+		.exec()
+		.await?
+		.into_iter()
+		.map(|checkpoint| (checkpoint.entity.clone(), checkpoint))
+		.collect::<HashMap<_, _>>();
+
+	// Resuming an interrupted backfill is fine, as it keeps the operations already generated,
+	// and so does deduplicating against them or only backfilling recent changes
+	if !config.force
+		&& !config.keeps_operations()
+		&& checkpoints.is_empty()
+		&& db
+			.crdt_operation()
+			.find_first(vec![crdt_operation::device_pub_id::equals(
+				sync.device_pub_id.to_db(),
+			)])
+			.exec()
+			.await?
+			.is_some()
+	{
+		return Err(Error::AlreadyBackfilled);
+	}
 
-	db._transaction()
-		.with_timeout(9_999_999_999)
+	let device_ids = backfilled_device_ids(db, local_device.id, config).await?;
+	let totals = count_entities(db, &device_ids, config).await?;
+
+	let ctx = BackfillContext {
+		db,
+		sync,
+		local_device: &local_device,
+		device_id: local_device.id,
+		device_ids: &device_ids,
+		config,
+		totals: &totals,
+		checkpoints: &checkpoints,
+		on_progress: &on_progress,
+		cancellation_token,
+		dry_run: false,
+		save_checkpoints: true,
+	};
+
+	let report = if config.best_effort {
+		backfill_best_effort(ctx).await?
+	} else if let Some(chunking) = config.chunking {
+		backfill_chunked(ctx, chunking).await?
+	} else {
+		backfill_in_transaction(ctx).await?
+	};
+
+	// A single line per backfill, so it's easy to find in production logs
+	info!(
+		device_pub_id = %from_bytes_to_uuid(&local_device.pub_id),
+		elapsed = ?report.elapsed,
+		total_operations = report.total_operations(),
+		operations = ?report
+			.operations
+			.iter()
+			.map(|(entity, count)| (entity.name(), count))
+			.collect::<BTreeMap<_, _>>(),
+		failures = ?report
+			.failures
+			.iter()
+			.map(|(entity, _)| entity.name())
+			.collect::<Vec<_>>(),
+		"Backfill completed;",
+	);
+
+	Ok(report)
+}
+
+/// Backfills every entity in a single transaction, so a failure leaves no operation behind.
+async fn backfill_in_transaction(ctx: BackfillContext<'_>) -> Result<BackfillReport, Error> {
+	ctx.db
+		._transaction()
+		.with_timeout(ctx.config.transaction_timeout_ms())
 		.run(|db| async move {
+			let ctx = BackfillContext { db: &db, ..ctx };
+
 			debug!("backfill started");
 			let start = Instant::now();
-			db.crdt_operation()
-				.delete_many(vec![crdt_operation::device_pub_id::equals(
-					sync.device_pub_id.to_db(),
-				)])
-				.exec()
-				.await?;
 
-			backfill_device(&db, sync, local_device).await?;
+			clear_previous_operations(&ctx).await?;
 
-			(
-				backfill_volumes(&db, sync, local_device_id),
-				paginate_tags(&db, sync),
-				paginate_locations(&db, sync, local_device_id),
-				paginate_objects(&db, sync, local_device_id),
-				paginate_labels(&db, sync),
-			)
-				.try_join()
-				.await?;
+			let mut report = BackfillReport {
+				operations: backfill_scheduled(&ctx).await?,
+				..Default::default()
+			};
 
-			(
-				paginate_exif_datas(&db, sync, local_device_id),
-				paginate_file_paths(&db, sync, local_device_id),
-				paginate_tags_on_objects(&db, sync, local_device_id),
-				paginate_labels_on_objects(&db, sync, local_device_id),
-			)
-				.try_join()
-				.await?;
+			clear_checkpoints(&ctx).await?;
 
-			debug!(elapsed = ?start.elapsed(), "backfill ended");
+			report.elapsed = start.elapsed();
 
-			Ok(())
+			Ok(report)
 		})
 		.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn backfill_device(
-	db: &PrismaClient,
+/// Same as [`backfill_operations`], but committing in chunks with [`BackfillConfig::chunking`],
+/// so other library writes aren't blocked until the whole backfill completes.
+///
+/// This trades the atomicity of the backfill for not freezing the app on large libraries: a
+/// crash leaves the committed chunks behind, to be completed by the next backfill resuming
+/// from their checkpoints.
+pub async fn backfill_operations_chunked(
 	sync: &SyncManager,
-	local_device: device::Data,
-) -> Result<(), Error> {
-	db.crdt_operation()
-		.create_many(vec![crdt_op_unchecked_db(&sync.shared_create(
-			prisma_sync::device::SyncId {
-				pub_id: local_device.pub_id,
-			},
-			chain_optional_iter(
-				[],
-				[
-					option_sync_entry!(local_device.name, device::name),
-					option_sync_entry!(local_device.os, device::os),
-					option_sync_entry!(local_device.hardware_model, device::hardware_model),
-					option_sync_entry!(local_device.timestamp, device::timestamp),
-					option_sync_entry!(local_device.date_created, device::date_created),
-					option_sync_entry!(local_device.date_deleted, device::date_deleted),
-				],
-			),
-		))?])
+	chunking: BackfillChunking,
+) -> Result<BackfillReport, Error> {
+	let config = BackfillConfig {
+		chunking: Some(chunking),
+		..Default::default()
+	};
+
+	backfill_operations_with_progress(sync, &config, |_| {}).await
+}
+
+/// Regenerates the operations of a single `entity`, like after a syncable column was added to its
+/// model, without touching the operations of the other entities.
+///
+/// All the operations of the entity generated by the device are deleted and generated again
+/// from the current rows, in their own transaction. Returns how many operations were generated.
+///
+/// No checkpoints are stored, as the transaction leaves nothing behind to resume from anyway.
+pub async fn backfill_entity(sync: &SyncManager, entity: BackfillEntity) -> Result<u64, Error> {
+	let _lock_guard = sync.sync_lock.lock().await;
+
+	let db = &sync.db;
+	let config = BackfillConfig::default();
+
+	let local_device = db
+		.device()
+		.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
 		.exec()
-		.await?;
+		.await?
+		.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
 
-	Ok(())
+	let device_ids = backfilled_device_ids(db, local_device.id, &config).await?;
+	let totals = count_entities(db, &device_ids, &config).await?;
+	let checkpoints = HashMap::new();
+	let cancellation_token = CancellationToken::new();
+
+	let ctx = BackfillContext {
+		db,
+		sync,
+		local_device: &local_device,
+		device_id: local_device.id,
+		device_ids: &device_ids,
+		config: &config,
+		totals: &totals,
+		checkpoints: &checkpoints,
+		on_progress: &|_: BackfillProgress| {},
+		cancellation_token: &cancellation_token,
+		dry_run: false,
+		// Checkpoints would make the next backfill resume every other entity on top of their
+		// existing operations
+		save_checkpoints: false,
+	};
+
+	db._transaction()
+		.with_timeout(config.transaction_timeout_ms())
+		.run(|db| async move {
+			let ctx = BackfillContext { db: &db, ..ctx };
+
+			let deleted = db
+				.crdt_operation()
+				.delete_many(vec![
+					crdt_operation::device_pub_id::equals(sync.device_pub_id.to_db()),
+					crdt_operation::model::equals(i32::from(entity.model_id())),
+				])
+				.exec()
+				.await?;
+
+			debug!(?entity, deleted, "entity backfill started");
+
+			write_entity(&ctx, entity).await
+		})
+		.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn backfill_volumes(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
-	let Some(volume) = db
-		.volume()
-		.find_first(vec![volume::device_id::equals(Some(device_id))])
-		.include(volume::include!({device: select { pub_id }}))
+/// Goes through the same pagination and operation building as [`backfill_operations`], but
+/// only counts the operations that would be generated for each entity instead of writing them.
+///
+/// Nothing is written nor deleted, so it can be used to estimate how much a backfill would add
+/// to the `crdt_operation` table before running it. Existing checkpoints are ignored, counting
+/// the whole backfill.
+pub async fn backfill_operations_dry_run(sync: &SyncManager) -> Result<BackfillReport, Error> {
+	let db = &sync.db;
+	let config = BackfillConfig::default();
+
+	let local_device = db
+		.device()
+		.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
 		.exec()
 		.await?
-	else {
-		// Nothing to do
-		return Ok(());
+		.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
+
+	let device_ids = backfilled_device_ids(db, local_device.id, &config).await?;
+	let totals = count_entities(db, &device_ids, &config).await?;
+
+	debug!("backfill dry run started");
+	let start = Instant::now();
+
+	let ctx = BackfillContext {
+		db,
+		sync,
+		local_device: &local_device,
+		device_id: local_device.id,
+		device_ids: &device_ids,
+		config: &config,
+		totals: &totals,
+		checkpoints: &HashMap::new(),
+		on_progress: &|_: BackfillProgress| {},
+		cancellation_token: &CancellationToken::new(),
+		dry_run: true,
+		save_checkpoints: false,
 	};
 
-	db.crdt_operation()
-		.create_many(vec![crdt_op_unchecked_db(&sync.shared_create(
-			prisma_sync::volume::SyncId {
-				pub_id: volume.pub_id,
-			},
-			chain_optional_iter(
-				[
-					sync_entry!(volume.name, volume::name),
-					sync_entry!(volume.mount_type, volume::mount_type),
-					sync_entry!(volume.mount_point, volume::mount_point),
-					sync_entry!(volume.is_mounted, volume::is_mounted),
-					sync_entry!(volume.disk_type, volume::disk_type),
-					sync_entry!(volume.file_system, volume::file_system),
-					sync_entry!(volume.read_only, volume::read_only),
-					sync_entry!(volume.error_status, volume::error_status),
-					sync_entry!(volume.total_bytes_capacity, volume::total_bytes_capacity),
-					sync_entry!(volume.total_bytes_available, volume::total_bytes_available),
-				],
-				[option_sync_entry!(
-					volume.device.map(|device| {
-						prisma_sync::device::SyncId {
-							pub_id: device.pub_id,
-						}
-					}),
-					volume::device
-				)],
-			),
-		))?])
+	let report = BackfillReport {
+		operations: backfill_scheduled(&ctx).await?,
+		elapsed: start.elapsed(),
+		..Default::default()
+	};
+
+	debug!(
+		elapsed = ?report.elapsed,
+		total_operations = report.total_operations(),
+		"backfill dry run ended",
+	);
+
+	Ok(report)
+}
+
+/// Estimates how many rows [`backfill_operations`] would go through and how long it would take,
+/// so the user can decide when to enable sync, like when on Wi-Fi or charging.
+///
+/// Only counts rows, scoped by device like the backfill, so it's fast and writes nothing.
+pub async fn estimate_backfill(sync: &SyncManager) -> Result<BackfillEstimate, Error> {
+	estimate_backfill_with_config(sync, &BackfillConfig::default()).await
+}
+
+/// Same as [`estimate_backfill`], but counting the rows of a backfill with a custom
+/// [`BackfillConfig`], at its [`BackfillConfig::estimated_rows_per_second`].
+pub async fn estimate_backfill_with_config(
+	sync: &SyncManager,
+	config: &BackfillConfig,
+) -> Result<BackfillEstimate, Error> {
+	let db = &sync.db;
+
+	let local_device = db
+		.device()
+		.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
+		.select(device::select!({ id }))
 		.exec()
+		.await?
+		.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
+
+	let device_ids = backfilled_device_ids(db, local_device.id, config).await?;
+
+	let rows = count_entities(db, &device_ids, config)
+		.await?
+		.into_iter()
+		.collect::<BTreeMap<_, _>>();
+
+	let total_rows = rows.values().sum::<u64>();
+
+	#[allow(clippy::cast_precision_loss)]
+	let duration =
+		Duration::from_secs_f64(total_rows as f64 / config.estimated_rows_per_second.max(1) as f64);
+
+	Ok(BackfillEstimate {
+		rows,
+		total_rows,
+		duration,
+	})
+}
+
+/// Checks that every row backfilled by [`backfill_operations`] has its create operation, returning
+/// the entities where the number of rows and operations differ.
+///
+/// Rows are counted with the same device scoping as a backfill with the default
+/// [`BackfillConfig`]. Create operations generated after the backfill, or for rows deleted since,
+/// are counted too, so this is only accurate right after a backfill.
+pub async fn verify_backfill(sync: &SyncManager) -> Result<Vec<Discrepancy>, Error> {
+	let db = &sync.db;
+
+	let local_device = db
+		.device()
+		.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
+		.exec()
+		.await?
+		.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
+
+	let source_rows = count_entities(db, &[local_device.id], &BackfillConfig::default()).await?;
+
+	let operation_rows = BackfillEntity::ALL
+		.iter()
+		.map(|entity| {
+			db.crdt_operation()
+				.count(vec![
+					crdt_operation::device_pub_id::equals(sync.device_pub_id.to_db()),
+					crdt_operation::model::equals(i32::from(entity.model_id())),
+					crdt_operation::kind::equals(OperationKind::Create.to_string()),
+				])
+				.exec()
+		})
+		.collect::<Vec<_>>()
+		.try_join()
 		.await?;
 
-	Ok(())
+	let discrepancies = BackfillEntity::ALL
+		.into_iter()
+		.zip(operation_rows)
+		.map(|(model, operation_rows)| Discrepancy {
+			model,
+			source_rows: source_rows.get(&model).copied().unwrap_or_default(),
+			operation_rows: u64::try_from(operation_rows).unwrap_or_default(),
+		})
+		.filter(|discrepancy| discrepancy.source_rows != discrepancy.operation_rows)
+		.collect::<Vec<_>>();
+
+	if !discrepancies.is_empty() {
+		warn!(?discrepancies, "Backfill verification found discrepancies;");
+	}
+
+	Ok(discrepancies)
+}
+
+/// Finds the file paths with a `cas_id` backfilled by [`backfill_operations`] whose object
+/// wasn't backfilled along with them, like an object owned by another device, returning their
+/// pub_ids. Their operations reference an object the receiving devices won't know about.
+///
+/// Objects are scoped like a backfill with the default [`BackfillConfig`].
+pub async fn validate_file_path_object_links(sync: &SyncManager) -> Result<Vec<Uuid>, Error> {
+	let db = &sync.db;
+
+	let local_device = db
+		.device()
+		.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
+		.exec()
+		.await?
+		.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
+
+	let orphans = db
+		.file_path()
+		.find_many(vec![
+			file_path::device_id::equals(Some(local_device.id)),
+			file_path::cas_id::not(None),
+			file_path::object::is(vec![not![objects_device_filter(
+				&BackfillConfig::default(),
+				&[local_device.id]
+			)]]),
+		])
+		.select(file_path::select!({ pub_id }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|file_path| from_bytes_to_uuid(&file_path.pub_id))
+		.collect::<Vec<_>>();
+
+	if !orphans.is_empty() {
+		warn!(
+			orphans_count = orphans.len(),
+			"Found backfilled file paths linked to objects that weren't backfilled;",
+		);
+	}
+
+	Ok(orphans)
+}
+
+/// Generates the same operations as [`backfill_operations`], yielding them as they are built
+/// instead of writing them, so a consumer like a remote sync backend can start sending them
+/// while the following pages are still being fetched.
+///
+/// Nothing is written, so the existing operations and checkpoints of the device are left as is.
+pub fn stream_backfill_operations<'a>(
+	sync: &'a SyncManager,
+	config: &'a BackfillConfig,
+) -> impl Stream<Item = Result<CRDTOperation, Error>> + Send + 'a {
+	try_stream! {
+		let local_device = sync
+			.db
+			.device()
+			.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
+			.exec()
+			.await?
+			.ok_or(Error::DeviceNotFound(sync.device_pub_id.clone()))?;
+
+		let device_ids = backfilled_device_ids(&sync.db, local_device.id, config).await?;
+		let (totals, checkpoints) = (HashMap::new(), HashMap::new());
+		let cancellation_token = CancellationToken::new();
+
+		let ctx = BackfillContext {
+			db: &sync.db,
+			sync,
+			local_device: &local_device,
+			device_id: local_device.id,
+			device_ids: &device_ids,
+			config,
+			totals: &totals,
+			checkpoints: &checkpoints,
+			on_progress: &|_: BackfillProgress| {},
+			cancellation_token: &cancellation_token,
+			dry_run: true,
+			save_checkpoints: false,
+		};
+
+		for entity in BackfillPlan::default().entities() {
+			let mut pages = entity_pages(&ctx, entity);
+
+			while let Some(page) = pages.try_next().await? {
+				for operation in page.operations {
+					yield operation;
+				}
+			}
+		}
+	}
 }
 
-async fn paginate<T, E1, E2, E3, GetterFut, OperationsFut>(
-	getter: impl Fn(i32) -> GetterFut + Send,
-	id: impl Fn(&T) -> i32 + Send,
-	operations: impl Fn(Vec<T>) -> Result<OperationsFut, E3> + Send,
-) -> Result<(), Error>
+/// Backfills every entity of the [`BackfillPlan`], returning how many operations each generated.
+async fn backfill_scheduled(
+	ctx: &BackfillContext<'_>,
+) -> Result<BTreeMap<BackfillEntity, u64>, Error> {
+	if ctx.config.deterministic {
+		let mut operations = BTreeMap::new();
+
+		for entity in BackfillPlan::default().entities() {
+			ctx.ensure_not_cancelled()?;
+			operations.insert(entity, write_entity(ctx, entity).await?);
+		}
+
+		return Ok(operations);
+	}
+
+	schedule_entities(&BackfillPlan::default(), |entity| async move {
+		ctx.ensure_not_cancelled()?;
+		write_entity(ctx, entity).await
+	})
+	.await
+}
+
+/// Runs `f` concurrently for the entities of `plan`, starting each one as soon as its own
+/// dependencies completed, instead of waiting for their whole stage, so a large entity like
+/// [`BackfillEntity::Object`] doesn't hold back the entities that don't depend on it.
+///
+/// Stops at the first error, dropping the entities still running.
+async fn schedule_entities<Fut>(
+	plan: &BackfillPlan,
+	f: impl Fn(BackfillEntity) -> Fut,
+) -> Result<BTreeMap<BackfillEntity, u64>, Error>
 where
-	T: Send,
-	E1: Send,
-	E2: Send,
-	E3: Send,
-	Error: From<E1> + From<E2> + From<E3> + Send,
-	GetterFut: Future<Output = Result<Vec<T>, E1>> + Send,
-	OperationsFut: Future<Output = Result<i64, E2>> + Send,
+	Fut: Future<Output = Result<u64, Error>>,
 {
-	let mut next_cursor = Some(-1);
+	let mut pending = plan.entities().collect::<BTreeSet<_>>();
+	let mut unfinished = pending.clone();
+	let mut running = FuturesUnordered::new();
+	let mut operations = BTreeMap::new();
+
 	loop {
-		let Some(cursor) = next_cursor else {
+		let ready = pending
+			.iter()
+			.copied()
+			.filter(|entity| {
+				entity
+					.dependencies()
+					.iter()
+					.all(|dependency| !unfinished.contains(dependency))
+			})
+			.collect::<Vec<_>>();
+
+		for entity in ready {
+			pending.remove(&entity);
+			running.push(f(entity).map_ok(move |count| (entity, count)));
+		}
+
+		// The plan has no cycles, so something is always running until every entity completed
+		let Some((entity, count)) = running.try_next().await? else {
 			break;
 		};
 
-		let items = getter(cursor).await?;
-		next_cursor = items.last().map(&id);
-		operations(items)?.await?;
+		unfinished.remove(&entity);
+		operations.insert(entity, count);
+	}
+
+	Ok(operations)
+}
+
+/// Backfills each entity in its own transaction, collecting the failures instead of bailing
+/// out on the first one. Cancellation still stops the whole backfill.
+async fn backfill_best_effort(ctx: BackfillContext<'_>) -> Result<BackfillReport, Error> {
+	debug!("best effort backfill started");
+	let start = Instant::now();
+
+	ctx.db
+		._transaction()
+		.with_timeout(ctx.config.transaction_timeout_ms())
+		.run(
+			|db| async move { clear_previous_operations(&BackfillContext { db: &db, ..ctx }).await },
+		)
+		.await?;
+
+	let mut report = BackfillReport::default();
+
+	// Entities run one after the other, as SQLite only allows a single writing transaction
+	for entity in BackfillPlan::default().entities() {
+		ctx.ensure_not_cancelled()?;
+
+		let res = ctx
+			.db
+			._transaction()
+			.with_timeout(ctx.config.transaction_timeout_ms())
+			.run(
+				|db| async move { write_entity(&BackfillContext { db: &db, ..ctx }, entity).await },
+			)
+			.await;
+
+		match res {
+			Ok(count) => {
+				report.operations.insert(entity, count);
+			}
+			Err(Error::Cancelled) => return Err(Error::Cancelled),
+			Err(e) => {
+				warn!(?entity, ?e, "Failed to backfill entity, skipping it;");
+				report.failures.push((entity, e));
+			}
+		}
+	}
+
+	// Failed entities keep the checkpoints of their committed pages, if any, so a later
+	// backfill doesn't start over
+	if report.failures.is_empty() {
+		clear_checkpoints(&ctx).await?;
+	}
+
+	report.elapsed = start.elapsed();
+
+	Ok(report)
+}
+
+/// Backfills entity after entity, committing every chunk of pages in its own transaction.
+async fn backfill_chunked(
+	ctx: BackfillContext<'_>,
+	chunking: BackfillChunking,
+) -> Result<BackfillReport, Error> {
+	debug!(?chunking, "chunked backfill started");
+	let start = Instant::now();
+
+	ctx.db
+		._transaction()
+		.with_timeout(ctx.config.transaction_timeout_ms())
+		.run(
+			|db| async move { clear_previous_operations(&BackfillContext { db: &db, ..ctx }).await },
+		)
+		.await?;
+
+	let mut report = BackfillReport::default();
+
+	for entity in BackfillPlan::default().entities() {
+		ctx.ensure_not_cancelled()?;
+
+		let count = match chunking {
+			BackfillChunking::Entity => {
+				ctx.db
+					._transaction()
+					.with_timeout(ctx.config.transaction_timeout_ms())
+					.run(|db| async move {
+						write_entity(&BackfillContext { db: &db, ..ctx }, entity).await
+					})
+					.await?
+			}
+			BackfillChunking::Pages(pages_per_chunk) => {
+				write_entity_chunked(ctx, entity, pages_per_chunk.max(1)).await?
+			}
+		};
+
+		report.operations.insert(entity, count);
+	}
+
+	clear_checkpoints(&ctx).await?;
+
+	report.elapsed = start.elapsed();
+
+	Ok(report)
+}
+
+/// Deletes the operations left by previous backfills of the device, unless we're resuming one,
+/// deduplicating against them or only backfilling recent changes.
+async fn clear_previous_operations(ctx: &BackfillContext<'_>) -> Result<(), Error> {
+	if ctx.config.keeps_operations() {
+		debug!(since = ?ctx.config.since, "keeping existing operations");
+	} else if ctx.checkpoints.is_empty() {
+		ctx.db
+			.crdt_operation()
+			.delete_many(vec![crdt_operation::device_pub_id::equals(
+				ctx.sync.device_pub_id.to_db(),
+			)])
+			.exec()
+			.await?;
+	} else {
+		debug!(
+			entities = ?ctx.checkpoints.keys().collect::<Vec<_>>(),
+			"resuming backfill from checkpoints"
+		);
+	}
+
+	Ok(())
+}
+
+async fn clear_checkpoints(ctx: &BackfillContext<'_>) -> Result<(), Error> {
+	ctx.db
+		.backfill_checkpoint()
+		.delete_many(vec![backfill_checkpoint::device_pub_id::equals(
+			ctx.sync.device_pub_id.to_db(),
+		)])
+		.exec()
+		.await?;
+
+	Ok(())
+}
+
+/// Errors worth retrying, as they come from SQLite being busy with another writer.
+fn is_transient(e: &Error) -> bool {
+	match e {
+		Error::Database(e) => {
+			let message = e.to_string().to_lowercase();
+
+			[
+				"database is locked",
+				"database table is locked",
+				"database file is locked",
+			]
+			.iter()
+			.any(|locked| message.contains(locked))
+		}
+		_ => false,
+	}
+}
+
+/// Writes the operations of `entity` page by page, checkpointing and reporting progress
+/// after each one.
+#[instrument(skip(ctx, entity), fields(entity = entity.name()), err)]
+async fn write_entity(ctx: &BackfillContext<'_>, entity: BackfillEntity) -> Result<u64, Error> {
+	let mut pages = pin!(entity_pages(ctx, entity));
+
+	let mut processed = 0;
+	let mut operations_count = 0;
+
+	while let Some(page) = pages.try_next().await? {
+		processed += page.items_count;
+		operations_count += write_page(ctx, entity, page).await?;
+
+		ctx.report_progress(entity, processed);
+	}
+
+	ctx.report_progress(entity, processed);
+
+	Ok(operations_count)
+}
+
+/// Same as [`write_entity`], but fetching the pages with `ctx`'s client and writing every
+/// `pages_per_chunk` of them in their own transaction.
+#[instrument(skip(ctx, entity), fields(entity = entity.name()), err)]
+async fn write_entity_chunked(
+	ctx: BackfillContext<'_>,
+	entity: BackfillEntity,
+	pages_per_chunk: usize,
+) -> Result<u64, Error> {
+	let mut pages = pin!(entity_pages(&ctx, entity));
+
+	let mut chunk = Vec::with_capacity(pages_per_chunk);
+	let mut processed = 0;
+	let mut operations_count = 0;
+
+	loop {
+		let page = pages.try_next().await?;
+		let is_last = page.is_none();
+
+		if let Some(page) = page {
+			processed += page.items_count;
+			chunk.push(page);
+		}
+
+		if !chunk.is_empty() && (is_last || chunk.len() >= pages_per_chunk) {
+			let chunk = mem::take(&mut chunk);
+
+			operations_count += ctx
+				.db
+				._transaction()
+				.with_timeout(ctx.config.transaction_timeout_ms())
+				.run(|db| async move {
+					let ctx = BackfillContext { db: &db, ..ctx };
+
+					let mut count = 0;
+					for page in chunk {
+						count += write_page(&ctx, entity, page).await?;
+					}
+
+					Ok::<_, Error>(count)
+				})
+				.await?;
+
+			debug!(processed, "backfill chunk committed");
+		}
+
+		ctx.report_progress(entity, processed);
+
+		if is_last {
+			break;
+		}
+	}
+
+	Ok(operations_count)
+}
+
+/// Writes the operations of a page and checkpoints its cursor, returning how many operations
+/// were written.
+async fn write_page(
+	ctx: &BackfillContext<'_>,
+	entity: BackfillEntity,
+	page: Page,
+) -> Result<u64, Error> {
+	let operations = if ctx.config.deduplicates(entity) {
+		ctx.without_synced_records(page.operations).await?
+	} else {
+		page.operations
+	};
+
+	let count = if operations.is_empty() {
+		0
+	} else {
+		ctx.with_retry(|| ctx.write_operations(&operations)).await?
+	};
+
+	if let Some((cursor, item_cursor)) = page.next_cursor {
+		ctx.save_checkpoint(entity, cursor, item_cursor).await?;
+	}
+
+	Ok(count)
+}
+
+/// Generates the operations of `entity`, page by page, without writing them.
+fn entity_pages<'a>(
+	ctx: &'a BackfillContext<'a>,
+	entity: BackfillEntity,
+) -> BoxStream<'a, Result<Page, Error>> {
+	match entity {
+		BackfillEntity::Device => backfill_device(ctx).boxed(),
+		BackfillEntity::Volume => paginate_volumes(ctx).boxed(),
+		BackfillEntity::Tag => paginate_tags(ctx).boxed(),
+		BackfillEntity::Location => paginate_locations(ctx).boxed(),
+		BackfillEntity::Object => paginate_objects(ctx).boxed(),
+		BackfillEntity::Label => paginate_labels(ctx).boxed(),
+		BackfillEntity::Album => paginate_albums(ctx).boxed(),
+		BackfillEntity::SavedSearch => paginate_saved_searches(ctx).boxed(),
+		BackfillEntity::Preference => backfill_preferences(ctx).boxed(),
+		BackfillEntity::ExifData => paginate_exif_datas(ctx).boxed(),
+		BackfillEntity::FilePath => paginate_file_paths(ctx).boxed(),
+		BackfillEntity::TagOnObject => paginate_tags_on_objects(ctx).boxed(),
+		BackfillEntity::LabelOnObject => paginate_labels_on_objects(ctx).boxed(),
+		BackfillEntity::ObjectInAlbum => paginate_objects_in_albums(ctx).boxed(),
+		BackfillEntity::IndexerRule => paginate_indexer_rules(ctx).boxed(),
+		BackfillEntity::IndexerRulesInLocation => paginate_indexer_rules_in_location(ctx).boxed(),
+	}
+}
+
+/// Ids of the devices whose rows are backfilled with `config`, the local one first.
+async fn backfilled_device_ids(
+	db: &PrismaClient,
+	local_device_id: device::id::Type,
+	config: &BackfillConfig,
+) -> Result<Vec<device::id::Type>, Error> {
+	let other_device_ids = match &config.devices {
+		BackfillDevices::Local => return Ok(vec![local_device_id]),
+		BackfillDevices::Including(pub_ids) => {
+			let devices = db
+				.device()
+				.find_many(vec![device::pub_id::in_vec(
+					pub_ids.iter().map(DevicePubId::to_db).collect(),
+				)])
+				.select(device::select!({ id pub_id }))
+				.exec()
+				.await?;
+
+			if let Some(missing) = pub_ids.iter().find(|pub_id| {
+				let pub_id = pub_id.to_db();
+				!devices.iter().any(|device| device.pub_id == pub_id)
+			}) {
+				return Err(Error::DeviceNotFound(missing.clone()));
+			}
+
+			devices
+				.into_iter()
+				.map(|device| device.id)
+				.collect::<Vec<_>>()
+		}
+		BackfillDevices::All => db
+			.device()
+			.find_many(vec![])
+			.select(device::select!({ id }))
+			.exec()
+			.await?
+			.into_iter()
+			.map(|device| device.id)
+			.collect(),
+	};
+
+	Ok(iter::once(local_device_id)
+		.chain(
+			other_device_ids
+				.into_iter()
+				.filter(|&device_id| device_id != local_device_id),
+		)
+		.collect())
+}
+
+/// Counts the rows that will be backfilled for each entity, used as totals for progress reports.
+async fn count_entities(
+	db: &PrismaClient,
+	device_ids: &[device::id::Type],
+	config: &BackfillConfig,
+) -> Result<HashMap<BackfillEntity, u64>, Error> {
+	let since = config.since();
+
+	let (
+		volumes,
+		tags,
+		locations,
+		objects,
+		labels,
+		exif_datas,
+		file_paths,
+		tags_on_objects,
+		labels_on_objects,
+		albums,
+		objects_in_albums,
+		saved_searches,
+		preferences,
+		indexer_rules,
+		indexer_rules_in_locations,
+	) = (
+		db.volume()
+			.count(chain_optional_iter(
+				[volume::device_id::in_vec(owned_by(device_ids))],
+				[since.map(volume::date_modified::gte)],
+			))
+			.exec(),
+		db.tag()
+			.count(chain_optional_iter(
+				[],
+				[since.map(tag::date_modified::gte)],
+			))
+			.exec(),
+		db.location()
+			.count(vec![location::device_id::in_vec(owned_by(device_ids))])
+			.exec(),
+		db.object()
+			.count(vec![objects_device_filter(config, device_ids)])
+			.exec(),
+		db.label()
+			.count(chain_optional_iter(
+				[],
+				[since.map(label::date_modified::gte)],
+			))
+			.exec(),
+		db.exif_data()
+			.count(vec![exif_data::device_id::in_vec(owned_by(device_ids))])
+			.exec(),
+		db.file_path()
+			.count(chain_optional_iter(
+				[file_path::device_id::in_vec(owned_by(device_ids))],
+				[since.map(file_path::date_modified::gte)],
+			))
+			.exec(),
+		db.tag_on_object()
+			.count(vec![tag_on_object::device_id::in_vec(owned_by(device_ids))])
+			.exec(),
+		db.label_on_object()
+			.count(vec![label_on_object::device_id::in_vec(owned_by(
+				device_ids,
+			))])
+			.exec(),
+		db.album()
+			.count(chain_optional_iter(
+				[],
+				[since.map(album::date_modified::gte)],
+			))
+			.exec(),
+		db.object_in_album().count(vec![]).exec(),
+		db.saved_search()
+			.count(chain_optional_iter(
+				[],
+				[since.map(saved_search::date_modified::gte)],
+			))
+			.exec(),
+		db.preference().count(vec![]).exec(),
+		db.indexer_rule()
+			.count(chain_optional_iter(
+				[],
+				[since.map(indexer_rule::date_modified::gte)],
+			))
+			.exec(),
+		db.indexer_rules_in_location()
+			.count(vec![indexer_rules_in_location::location::is(vec![
+				location::device_id::in_vec(owned_by(device_ids)),
+			])])
+			.exec(),
+	)
+		.try_join()
+		.await?;
+
+	Ok([
+		(
+			BackfillEntity::Device,
+			i64::try_from(device_ids.len()).unwrap_or(i64::MAX),
+		),
+		(BackfillEntity::Volume, volumes),
+		(BackfillEntity::Tag, tags),
+		(BackfillEntity::Location, locations),
+		(BackfillEntity::Object, objects),
+		(BackfillEntity::Label, labels),
+		(BackfillEntity::ExifData, exif_datas),
+		(BackfillEntity::FilePath, file_paths),
+		(BackfillEntity::TagOnObject, tags_on_objects),
+		(BackfillEntity::LabelOnObject, labels_on_objects),
+		(BackfillEntity::Album, albums),
+		(BackfillEntity::ObjectInAlbum, objects_in_albums),
+		(BackfillEntity::SavedSearch, saved_searches),
+		(BackfillEntity::Preference, preferences),
+		(BackfillEntity::IndexerRule, indexer_rules),
+		(
+			BackfillEntity::IndexerRulesInLocation,
+			indexer_rules_in_locations,
+		),
+	]
+	.into_iter()
+	.map(|(entity, count)| (entity, u64::try_from(count).unwrap_or_default()))
+	.collect())
+}
+
+/// Operations of a page of rows, generated by [`entity_pages`].
+struct Page {
+	operations: Vec<CRDTOperation>,
+	/// How many rows the operations were generated from.
+	items_count: u64,
+	/// Cursor and item cursor to checkpoint once the page is written.
+	next_cursor: Option<(i32, Option<i32>)>,
+}
+
+fn backfill_device<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		local_device,
+		device_ids,
+		..
+	} = *ctx;
+
+	try_stream! {
+		if ctx.checkpoint(BackfillEntity::Device).is_some() {
+			// Already backfilled by a previous run
+			return;
+		}
+
+		let other_devices = if device_ids.len() > 1 {
+			db.device()
+				.find_many(vec![device::id::in_vec(device_ids[1..].to_vec())])
+				.exec()
+				.await?
+		} else {
+			vec![]
+		};
+
+		let operations = iter::once(local_device)
+			.chain(&other_devices)
+			.map(|device_data| {
+				sync.shared_create(
+					prisma_sync::device::SyncId {
+						pub_id: device_data.pub_id.clone(),
+					},
+					chain_optional_iter(
+						[],
+						[
+							option_sync_entry!(device_data.name.clone(), device::name),
+							option_sync_entry!(device_data.os, device::os),
+							option_sync_entry!(device_data.hardware_model, device::hardware_model),
+							option_sync_entry!(device_data.timestamp, device::timestamp),
+							option_sync_entry!(device_data.date_created, device::date_created),
+							option_sync_entry!(device_data.date_deleted, device::date_deleted),
+						],
+					),
+				)
+			})
+			.collect::<Vec<_>>();
+
+		yield Page {
+			items_count: operations.len() as u64,
+			operations,
+			next_cursor: Some((local_device.id, None)),
+		};
+	}
+}
+
+/// A device can have many volumes, so they're paginated like any other table.
+fn paginate_volumes<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		config,
+		..
+	} = *ctx;
+	let since = config.since();
+
+	paginate(
+		ctx,
+		BackfillEntity::Volume,
+		move |cursor, take| async move {
+			let volumes = db
+				.volume()
+				.find_many(chain_optional_iter(
+					[
+						volume::id::gt(cursor),
+						volume::device_id::in_vec(owned_by(device_ids)),
+					],
+					[since.map(volume::date_modified::gte)],
+				))
+				.order_by(volume::id::order(SortOrder::Asc))
+				.take(take)
+				.include(volume::include!({device: select { pub_id }}))
+				.exec()
+				.await?;
+
+			if config.strict {
+				if let Some(volume) = volumes.iter().find(|volume| volume.device.is_none()) {
+					return Err(Error::OrphanVolume(from_bytes_to_uuid(&volume.pub_id)));
+				}
+			}
+
+			Ok(volumes)
+		},
+		|volume| volume.id,
+		move |volumes| {
+			volumes
+				.into_iter()
+				.map(|v| {
+					if v.device.is_none() {
+						warn!(
+							pub_id = %from_bytes_to_uuid(&v.pub_id),
+							"Volume has no device, backfilling it without one;",
+						);
+					}
+
+					sync.shared_create(
+						prisma_sync::volume::SyncId { pub_id: v.pub_id },
+						chain_optional_iter(
+							[
+								sync_entry!(v.name, volume::name),
+								sync_entry!(v.mount_type, volume::mount_type),
+								sync_entry!(v.mount_point, volume::mount_point),
+								sync_entry!(v.is_mounted, volume::is_mounted),
+								sync_entry!(v.disk_type, volume::disk_type),
+								sync_entry!(v.file_system, volume::file_system),
+								sync_entry!(v.read_only, volume::read_only),
+								sync_entry!(v.error_status, volume::error_status),
+								sync_entry!(v.total_bytes_capacity, volume::total_bytes_capacity),
+								sync_entry!(v.total_bytes_available, volume::total_bytes_available),
+							],
+							[option_sync_entry!(
+								v.device.map(|device| {
+									prisma_sync::device::SyncId {
+										pub_id: device.pub_id,
+									}
+								}),
+								volume::device
+							)],
+						),
+					)
+				})
+				.collect()
+		},
+	)
+}
+
+/// Preferences are a handful of rows keyed by name, so they're all backfilled at once.
+fn backfill_preferences<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+
+	try_stream! {
+		if ctx.checkpoint(BackfillEntity::Preference).is_some() {
+			// Already backfilled by a previous run
+			return;
+		}
+
+		let preferences = db.preference().find_many(vec![]).exec().await?;
+
+		if preferences.is_empty() {
+			return;
+		}
+
+		yield Page {
+			items_count: preferences.len() as u64,
+			operations: preferences
+				.into_iter()
+				.map(|p| {
+					sync.shared_create(
+						prisma_sync::preference::SyncId { key: p.key },
+						chain_optional_iter([], [option_sync_entry!(p.value, preference::value)]),
+					)
+				})
+				.collect(),
+			// Preferences have no numeric id, the checkpoint only marks them as done
+			next_cursor: Some((0, None)),
+		};
+	}
+}
+
+/// Backfill flavor of [`pagination::paginate`], resuming from the entity's checkpoint,
+/// retrying failed pages, adapting the page size and turning each page into operations.
+fn paginate<'a, T, E, GetterFut>(
+	ctx: &'a BackfillContext<'a>,
+	entity: BackfillEntity,
+	getter: impl Fn(i32, i64) -> GetterFut + Send + Sync + 'a,
+	id: impl Fn(&T) -> i32 + Send + Sync + 'a,
+	operations: impl Fn(Vec<T>) -> Vec<CRDTOperation> + Send + Sync + 'a,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a
+where
+	T: Send + 'a,
+	E: Send + 'a,
+	Error: From<E>,
+	GetterFut: Future<Output = Result<Vec<T>, E>> + Send + 'a,
+{
+	try_stream! {
+		let start = ctx
+			.checkpoint(entity)
+			.map_or(-1, |checkpoint| checkpoint.cursor);
+
+		let take = &AtomicI64::new(ctx.page_size());
+		let getter = &getter;
+
+		let mut pages = pin!(pagination::paginate(
+			start,
+			move |cursor| async move {
+				ctx.ensure_not_cancelled()?;
+
+				let take = take.load(Ordering::Relaxed);
+				ctx.with_retry(|| getter(cursor, take).err_into()).await
+			},
+			&id,
+		)
+		.map_err(|e| match e {
+			PaginationError::Getter(e) => e,
+			PaginationError::NonMonotonicCursor { previous, next } => Error::NonMonotonicCursor {
+				entity: entity.name(),
+				previous: (previous, None),
+				next: (next, None),
+			},
+		}));
+
+		let mut fetched_any = false;
+
+		while let Some(items) = pages.try_next().await? {
+			let page = Page {
+				next_cursor: items.last().map(|item| (id(item), None)),
+				items_count: items.len() as u64,
+				operations: operations(items),
+			};
+
+			debug!(
+				cursor = ?page.next_cursor,
+				items_count = page.items_count,
+				"backfill page fetched",
+			);
+
+			fetched_any = true;
+			take.store(
+				ctx.next_page_size(take.load(Ordering::Relaxed), &page.operations)?,
+				Ordering::Relaxed,
+			);
+
+			yield page;
+		}
+
+		if !fetched_any && ctx.checkpoint(entity).is_none() {
+			ctx.check_no_rows(entity).await?;
+		}
+	}
+}
+
+/// Same as [`paginate`], but fetching up to [`BackfillConfig::parallel_pages`] pages at once,
+/// splitting the ids from the first row up to `max_id` in ranges of `page_size` ids.
+///
+/// Meant for large tables with a mostly contiguous id space, as each range still fetches a whole
+/// page, discarding the rows past its end. Pages are fetched concurrently but yielded in cursor
+/// order, each checkpointed at the end of its range, so an interrupted backfill never
+/// regenerates a range that was already written.
+fn paginate_parallel<'a, T, E, GetterFut, MaxIdFut>(
+	ctx: &'a BackfillContext<'a>,
+	entity: BackfillEntity,
+	max_id: MaxIdFut,
+	getter: impl Fn(i32, i64) -> GetterFut + Send + Sync + 'a,
+	id: impl Fn(&T) -> i32 + Send + Sync + 'a,
+	operations: impl Fn(Vec<T>) -> Vec<CRDTOperation> + Send + Sync + 'a,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a
+where
+	T: Send + 'a,
+	E: Send + 'a,
+	Error: From<E>,
+	GetterFut: Future<Output = Result<Vec<T>, E>> + Send + 'a,
+	MaxIdFut: Future<Output = Result<Option<i32>, E>> + Send + 'a,
+{
+	try_stream! {
+		if ctx.config.parallel_pages() <= 1 {
+			let mut pages = pin!(paginate(ctx, entity, getter, id, operations));
+
+			while let Some(page) = pages.try_next().await? {
+				yield page;
+			}
+
+			return;
+		}
+
+		let start = ctx
+			.checkpoint(entity)
+			.map_or(-1, |checkpoint| checkpoint.cursor);
+
+		let first_id = getter(start, 1).await?.first().map(&id);
+
+		let (Some(first_id), Some(max_id)) = (first_id, max_id.await?) else {
+			if start < 0 {
+				ctx.check_no_rows(entity).await?;
+			}
+			return;
+		};
+
+		let page_size = i32::try_from(ctx.page_size()).unwrap_or(i32::MAX);
+
+		let ranges = (first_id - 1..max_id)
+			.step_by(ctx.config.page_size.max(1))
+			.map(|after| (after, after.saturating_add(page_size).min(max_id)))
+			.collect::<Vec<_>>();
+
+		let (getter, id, operations) = (&getter, &id, &operations);
+
+		let mut pages = pin!(stream::iter(ranges.iter().copied())
+			.map(|(after, up_to)| async move {
+				ctx.ensure_not_cancelled()?;
+
+				ctx.with_retry(|| async {
+					let mut items = getter(after, ctx.page_size()).await?;
+					items.retain(|item| id(item) <= up_to);
+
+					Ok::<_, Error>((up_to, items.len() as u64, operations(items)))
+				})
+				.await
+			})
+			// Keeps the pages in order, as a page completing early can't be checkpointed past
+			// the ones still being fetched
+			.buffered(ctx.config.parallel_pages()));
+
+		while let Some((up_to, items_count, page_operations)) = pages.try_next().await? {
+			debug!(up_to, items_count, "backfill page fetched");
+
+			yield Page {
+				operations: page_operations,
+				items_count,
+				next_cursor: Some((up_to, None)),
+			};
+		}
+	}
+}
+
+/// Backfill flavor of [`pagination::paginate_relation`], like [`paginate`].
+fn paginate_relation<'a, T, E, GetterFut>(
+	ctx: &'a BackfillContext<'a>,
+	entity: BackfillEntity,
+	getter: impl Fn(i32, i32, i64) -> GetterFut + Send + Sync + 'a,
+	id: impl Fn(&T) -> (i32, i32) + Send + Sync + 'a,
+	operations: impl Fn(Vec<T>) -> Vec<CRDTOperation> + Send + Sync + 'a,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a
+where
+	T: Send + 'a,
+	E: Send + 'a,
+	Error: From<E>,
+	GetterFut: Future<Output = Result<Vec<T>, E>> + Send + 'a,
+{
+	try_stream! {
+		let start = ctx.checkpoint(entity).map_or((-1, -1), |checkpoint| {
+			(checkpoint.cursor, checkpoint.item_cursor.unwrap_or(-1))
+		});
+
+		let take = &AtomicI64::new(ctx.page_size());
+		let getter = &getter;
+
+		let mut pages = pin!(pagination::paginate_relation(
+			start,
+			move |group_id, item_id| async move {
+				ctx.ensure_not_cancelled()?;
+
+				let take = take.load(Ordering::Relaxed);
+				ctx.with_retry(|| getter(group_id, item_id, take).err_into())
+					.await
+			},
+			&id,
+		)
+		.map_err(|e| match e {
+			PaginationError::Getter(e) => e,
+			PaginationError::NonMonotonicCursor { previous, next } => Error::NonMonotonicCursor {
+				entity: entity.name(),
+				previous: (previous.0, Some(previous.1)),
+				next: (next.0, Some(next.1)),
+			},
+		}));
+
+		let mut fetched_any = false;
+		let mut seen_records = HashSet::new();
+
+		while let Some(items) = pages.try_next().await? {
+			let mut page = Page {
+				next_cursor: items.last().map(|item| {
+					let (group_id, item_id) = id(item);
+					(group_id, Some(item_id))
+				}),
+				items_count: items.len() as u64,
+				operations: operations(items),
+			};
+
+			debug!(
+				cursor = ?page.next_cursor,
+				items_count = page.items_count,
+				"backfill page fetched",
+			);
+
+			if ctx.config.deduplicate_relations {
+				skip_seen_records(entity, &mut page.operations, &mut seen_records)?;
+			}
+
+			fetched_any = true;
+			take.store(
+				ctx.next_page_size(take.load(Ordering::Relaxed), &page.operations)?,
+				Ordering::Relaxed,
+			);
+
+			yield page;
+		}
+
+		if !fetched_any && ctx.checkpoint(entity).is_none() {
+			ctx.check_no_rows(entity).await?;
+		}
 	}
-
-	Ok(())
 }
 
-async fn paginate_relation<T, E1, E2, E3, GetterFut, OperationsFut>(
-	getter: impl Fn(i32, i32) -> GetterFut + Send,
-	id: impl Fn(&T) -> (i32, i32) + Send,
-	operations: impl Fn(Vec<T>) -> Result<OperationsFut, E3> + Send,
-) -> Result<(), Error>
-where
-	T: Send,
-	E1: Send,
-	E2: Send,
-	E3: Send,
-	Error: From<E1> + From<E2> + From<E3> + Send,
-	GetterFut: Future<Output = Result<Vec<T>, E1>> + Send,
-	OperationsFut: Future<Output = Result<i64, E2>> + Send,
-{
-	let mut next_cursor = Some((-1, -1));
-	loop {
-		let Some(cursor) = next_cursor else {
-			break;
-		};
+/// Drops the operations whose record is in `seen_records`, adding the records of the others.
+fn skip_seen_records(
+	entity: BackfillEntity,
+	operations: &mut Vec<CRDTOperation>,
+	seen_records: &mut HashSet<Vec<u8>>,
+) -> Result<(), Error> {
+	// Record ids aren't hashable, so they're keyed by their encoding
+	let keys = operations
+		.iter()
+		.map(|op| rmp_serde::to_vec(&op.record_id))
+		.collect::<Result<Vec<_>, _>>()?;
 
-		let items = getter(cursor.0, cursor.1).await?;
-		next_cursor = items.last().map(&id);
-		operations(items)?.await?;
-	}
+	let mut keys = keys.into_iter();
+
+	operations.retain(|op| {
+		let is_new = keys.next().is_some_and(|key| seen_records.insert(key));
+
+		if !is_new {
+			warn!(
+				entity = entity.name(),
+				record_id = %op.record_id,
+				"Skipping duplicate relation in backfill;",
+			);
+		}
+
+		is_new
+	});
 
 	Ok(())
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_tags(db: &PrismaClient, sync: &SyncManager) -> Result<(), Error> {
+fn paginate_tags<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+	let since = ctx.config.since();
+
 	paginate(
-		|cursor| {
+		ctx,
+		BackfillEntity::Tag,
+		move |cursor, take| {
 			db.tag()
-				.find_many(vec![tag::id::gt(cursor)])
+				.find_many(chain_optional_iter(
+					[tag::id::gt(cursor)],
+					[since.map(tag::date_modified::gte)],
+				))
 				.order_by(tag::id::order(SortOrder::Asc))
+				.take(take)
 				.exec()
 		},
 		|tag| tag.id,
-		|tags| {
+		move |tags| {
 			tags.into_iter()
 				.map(|t| {
 					sync.shared_create(
@@ -234,36 +2177,39 @@ async fn paginate_tags(db: &PrismaClient, sync: &SyncManager) -> Result<(), Erro
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_locations(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
+fn paginate_locations<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+
 	paginate(
-		|cursor| {
+		ctx,
+		BackfillEntity::Location,
+		move |cursor, take| {
 			db.location()
 				.find_many(vec![
 					location::id::gt(cursor),
-					location::device_id::equals(Some(device_id)),
+					location::device_id::in_vec(owned_by(device_ids)),
 				])
 				.order_by(location::id::order(SortOrder::Asc))
-				.take(1000)
+				.take(take)
 				.include(location::include!({
 					device: select { pub_id }
 				}))
 				.exec()
 		},
 		|location| location.id,
-		|locations| {
+		move |locations| {
 			locations
 				.into_iter()
 				.map(|l| {
@@ -303,36 +2249,66 @@ async fn paginate_locations(
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_objects(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
-	paginate(
-		|cursor| {
+/// Objects owned by the devices, and the ones without a device when
+/// [`BackfillConfig::include_deviceless_objects`] is set.
+fn objects_device_filter(
+	config: &BackfillConfig,
+	device_ids: &[device::id::Type],
+) -> object::WhereParam {
+	if config.include_deviceless_objects {
+		or![
+			object::device_id::in_vec(owned_by(device_ids)),
+			object::device_id::equals(None)
+		]
+	} else {
+		object::device_id::in_vec(owned_by(device_ids))
+	}
+}
+
+/// Values of the nullable `device_id` column of the rows owned by `device_ids`.
+fn owned_by(device_ids: &[device::id::Type]) -> Vec<Option<device::id::Type>> {
+	device_ids.iter().copied().map(Some).collect()
+}
+
+fn paginate_objects<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+
+	paginate_parallel(
+		ctx,
+		BackfillEntity::Object,
+		db.object()
+			.find_first(vec![objects_device_filter(ctx.config, device_ids)])
+			.order_by(object::id::order(SortOrder::Desc))
+			.select(object::select!({ id }))
+			.exec()
+			.map_ok(|object| object.map(|object| object.id)),
+		move |cursor, take| {
 			db.object()
 				.find_many(vec![
 					object::id::gt(cursor),
-					object::device_id::equals(Some(device_id)),
+					objects_device_filter(ctx.config, device_ids),
 				])
 				.order_by(object::id::order(SortOrder::Asc))
-				.take(1000)
+				.take(take)
 				.include(object::include!({
 					device: select { pub_id }
 				}))
 				.exec()
 		},
 		|object| object.id,
-		|objects| {
+		move |objects| {
 			objects
 				.into_iter()
 				.map(|o| {
@@ -360,29 +2336,32 @@ async fn paginate_objects(
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_exif_datas(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
+fn paginate_exif_datas<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+
 	paginate(
-		|cursor| {
+		ctx,
+		BackfillEntity::ExifData,
+		move |cursor, take| {
 			db.exif_data()
 				.find_many(vec![
 					exif_data::id::gt(cursor),
-					exif_data::device_id::equals(Some(device_id)),
+					exif_data::device_id::in_vec(owned_by(device_ids)),
 				])
 				.order_by(exif_data::id::order(SortOrder::Asc))
-				.take(1000)
+				.take(take)
 				.include(exif_data::include!({
 					object: select { pub_id }
 					device: select { pub_id }
@@ -390,7 +2369,7 @@ async fn paginate_exif_datas(
 				.exec()
 		},
 		|ed| ed.id,
-		|exif_datas| {
+		move |exif_datas| {
 			exif_datas
 				.into_iter()
 				.map(|ed| {
@@ -424,28 +2403,42 @@ async fn paginate_exif_datas(
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_file_paths(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
-	paginate(
-		|cursor| {
+fn paginate_file_paths<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+	let since = ctx.config.since();
+
+	paginate_parallel(
+		ctx,
+		BackfillEntity::FilePath,
+		db.file_path()
+			.find_first(vec![file_path::device_id::in_vec(owned_by(device_ids))])
+			.order_by(file_path::id::order(SortOrder::Desc))
+			.select(file_path::select!({ id }))
+			.exec()
+			.map_ok(|file_path| file_path.map(|file_path| file_path.id)),
+		move |cursor, take| {
 			db.file_path()
-				.find_many(vec![
-					file_path::id::gt(cursor),
-					file_path::device_id::equals(Some(device_id)),
-				])
+				.find_many(chain_optional_iter(
+					[
+						file_path::id::gt(cursor),
+						file_path::device_id::in_vec(owned_by(device_ids)),
+					],
+					[since.map(file_path::date_modified::gte)],
+				))
 				.order_by(file_path::id::order(SortOrder::Asc))
+				.take(take)
 				.include(file_path::include!({
 					location: select { pub_id }
 					object: select { pub_id }
@@ -454,7 +2447,7 @@ async fn paginate_file_paths(
 				.exec()
 		},
 		|o| o.id,
-		|file_paths| {
+		move |file_paths| {
 			file_paths
 				.into_iter()
 				.map(|fp| {
@@ -508,30 +2501,39 @@ async fn paginate_file_paths(
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_tags_on_objects(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
+fn paginate_tags_on_objects<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+
 	paginate_relation(
-		|group_id, item_id| {
+		ctx,
+		BackfillEntity::TagOnObject,
+		move |group_id, item_id, take| {
 			db.tag_on_object()
 				.find_many(vec![
-					tag_on_object::tag_id::gt(group_id),
-					tag_on_object::object_id::gt(item_id),
-					tag_on_object::device_id::equals(Some(device_id)),
+					or![
+						tag_on_object::tag_id::gt(group_id),
+						and![
+							tag_on_object::tag_id::equals(group_id),
+							tag_on_object::object_id::gt(item_id)
+						]
+					],
+					tag_on_object::device_id::in_vec(owned_by(device_ids)),
 				])
 				.order_by(tag_on_object::tag_id::order(SortOrder::Asc))
 				.order_by(tag_on_object::object_id::order(SortOrder::Asc))
+				.take(take)
 				.include(tag_on_object::include!({
 					tag: select { pub_id }
 					object: select { pub_id }
@@ -540,7 +2542,7 @@ async fn paginate_tags_on_objects(
 				.exec()
 		},
 		|t_o| (t_o.tag_id, t_o.object_id),
-		|tag_on_objects| {
+		move |tag_on_objects| {
 			tag_on_objects
 				.into_iter()
 				.map(|t_o| {
@@ -569,25 +2571,57 @@ async fn paginate_tags_on_objects(
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_labels(db: &PrismaClient, sync: &SyncManager) -> Result<(), Error> {
+/// Labels are synced by name, so two labels with the same name would silently collide on other
+/// devices. Names are unique in the schema, so this only fails on a corrupted database.
+fn paginate_labels<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	try_stream! {
+		let mut pages = pin!(paginate_labels_unchecked(ctx));
+		let mut seen_names = HashSet::new();
+
+		while let Some(page) = pages.try_next().await? {
+			for op in &page.operations {
+				// Record ids aren't hashable, so they're keyed by their encoding
+				if !seen_names.insert(rmp_serde::to_vec(&op.record_id)?) {
+					Err(Error::DuplicateSyncId {
+						entity: BackfillEntity::Label.name(),
+						record_id: op.record_id.to_string(),
+					})?;
+				}
+			}
+
+			yield page;
+		}
+	}
+}
+
+fn paginate_labels_unchecked<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+	let since = ctx.config.since();
+
 	paginate(
-		|cursor| {
+		ctx,
+		BackfillEntity::Label,
+		move |cursor, take| {
 			db.label()
-				.find_many(vec![label::id::gt(cursor)])
+				.find_many(chain_optional_iter(
+					[label::id::gt(cursor)],
+					[since.map(label::date_modified::gte)],
+				))
 				.order_by(label::id::order(SortOrder::Asc))
+				.take(take)
 				.exec()
 		},
 		|label| label.id,
-		|labels| {
+		move |labels| {
 			labels
 				.into_iter()
 				.map(|l| {
@@ -602,30 +2636,39 @@ async fn paginate_labels(db: &PrismaClient, sync: &SyncManager) -> Result<(), Er
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
 }
 
-#[instrument(skip(db, sync), err)]
-async fn paginate_labels_on_objects(
-	db: &PrismaClient,
-	sync: &SyncManager,
-	device_id: device::id::Type,
-) -> Result<(), Error> {
+fn paginate_labels_on_objects<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+
 	paginate_relation(
-		|group_id, item_id| {
+		ctx,
+		BackfillEntity::LabelOnObject,
+		move |group_id, item_id, take| {
 			db.label_on_object()
 				.find_many(vec![
-					label_on_object::label_id::gt(group_id),
-					label_on_object::object_id::gt(item_id),
-					label_on_object::device_id::equals(Some(device_id)),
+					or![
+						label_on_object::label_id::gt(group_id),
+						and![
+							label_on_object::label_id::equals(group_id),
+							label_on_object::object_id::gt(item_id)
+						]
+					],
+					label_on_object::device_id::in_vec(owned_by(device_ids)),
 				])
 				.order_by(label_on_object::label_id::order(SortOrder::Asc))
 				.order_by(label_on_object::object_id::order(SortOrder::Asc))
+				.take(take)
 				.include(label_on_object::include!({
 					object: select { pub_id }
 					label: select { name }
@@ -634,7 +2677,7 @@ async fn paginate_labels_on_objects(
 				.exec()
 		},
 		|l_o| (l_o.label_id, l_o.object_id),
-		|label_on_objects| {
+		move |label_on_objects| {
 			label_on_objects
 				.into_iter()
 				.map(|l_o| {
@@ -660,10 +2703,710 @@ async fn paginate_labels_on_objects(
 						),
 					)
 				})
-				.map(|o| crdt_op_unchecked_db(&o))
-				.collect::<Result<Vec<_>, _>>()
-				.map(|creates| db.crdt_operation().create_many(creates).exec())
+				.collect()
 		},
 	)
-	.await
+}
+
+fn paginate_albums<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+	let since = ctx.config.since();
+
+	paginate(
+		ctx,
+		BackfillEntity::Album,
+		move |cursor, take| {
+			db.album()
+				.find_many(chain_optional_iter(
+					[album::id::gt(cursor)],
+					[since.map(album::date_modified::gte)],
+				))
+				.order_by(album::id::order(SortOrder::Asc))
+				.take(take)
+				.exec()
+		},
+		|album| album.id,
+		move |albums| {
+			albums
+				.into_iter()
+				.map(|a| {
+					sync.shared_create(
+						prisma_sync::album::SyncId { pub_id: a.pub_id },
+						chain_optional_iter(
+							[],
+							[
+								option_sync_entry!(a.name, album::name),
+								option_sync_entry!(a.is_hidden, album::is_hidden),
+								option_sync_entry!(a.date_created, album::date_created),
+								option_sync_entry!(a.date_modified, album::date_modified),
+							],
+						),
+					)
+				})
+				.collect()
+		},
+	)
+}
+
+/// Albums have no device, so like for tags and labels, every device backfills all of them.
+fn paginate_objects_in_albums<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+
+	paginate_relation(
+		ctx,
+		BackfillEntity::ObjectInAlbum,
+		move |group_id, item_id, take| {
+			db.object_in_album()
+				.find_many(vec![or![
+					object_in_album::album_id::gt(group_id),
+					and![
+						object_in_album::album_id::equals(group_id),
+						object_in_album::object_id::gt(item_id)
+					]
+				]])
+				.order_by(object_in_album::album_id::order(SortOrder::Asc))
+				.order_by(object_in_album::object_id::order(SortOrder::Asc))
+				.take(take)
+				.include(object_in_album::include!({
+					album: select { pub_id }
+					object: select { pub_id }
+				}))
+				.exec()
+		},
+		|o_a| (o_a.album_id, o_a.object_id),
+		move |objects_in_albums| {
+			objects_in_albums
+				.into_iter()
+				.map(|o_a| {
+					sync.relation_create(
+						prisma_sync::object_in_album::SyncId {
+							album: prisma_sync::album::SyncId {
+								pub_id: o_a.album.pub_id,
+							},
+							object: prisma_sync::object::SyncId {
+								pub_id: o_a.object.pub_id,
+							},
+						},
+						chain_optional_iter(
+							[],
+							[option_sync_entry!(
+								o_a.date_created,
+								object_in_album::date_created
+							)],
+						),
+					)
+				})
+				.collect()
+		},
+	)
+}
+
+fn paginate_saved_searches<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+	let since = ctx.config.since();
+
+	paginate(
+		ctx,
+		BackfillEntity::SavedSearch,
+		move |cursor, take| {
+			db.saved_search()
+				.find_many(chain_optional_iter(
+					[saved_search::id::gt(cursor)],
+					[since.map(saved_search::date_modified::gte)],
+				))
+				.order_by(saved_search::id::order(SortOrder::Asc))
+				.take(take)
+				.exec()
+		},
+		|saved_search| saved_search.id,
+		move |saved_searches| {
+			saved_searches
+				.into_iter()
+				.map(|s| {
+					sync.shared_create(
+						prisma_sync::saved_search::SyncId { pub_id: s.pub_id },
+						chain_optional_iter(
+							[],
+							[
+								option_sync_entry!(s.target, saved_search::target),
+								option_sync_entry!(s.search, saved_search::search),
+								option_sync_entry!(s.filters, saved_search::filters),
+								option_sync_entry!(s.name, saved_search::name),
+								option_sync_entry!(s.icon, saved_search::icon),
+								option_sync_entry!(s.description, saved_search::description),
+								option_sync_entry!(s.date_created, saved_search::date_created),
+								option_sync_entry!(s.date_modified, saved_search::date_modified),
+							],
+						),
+					)
+				})
+				.collect()
+		},
+	)
+}
+
+/// Indexer rules have no device, so like for tags and labels, every device backfills all of them.
+fn paginate_indexer_rules<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext { db, sync, .. } = *ctx;
+	let since = ctx.config.since();
+
+	paginate(
+		ctx,
+		BackfillEntity::IndexerRule,
+		move |cursor, take| {
+			db.indexer_rule()
+				.find_many(chain_optional_iter(
+					[indexer_rule::id::gt(cursor)],
+					[since.map(indexer_rule::date_modified::gte)],
+				))
+				.order_by(indexer_rule::id::order(SortOrder::Asc))
+				.take(take)
+				.exec()
+		},
+		|indexer_rule| indexer_rule.id,
+		move |indexer_rules| {
+			indexer_rules
+				.into_iter()
+				.map(|r| {
+					sync.shared_create(
+						prisma_sync::indexer_rule::SyncId { pub_id: r.pub_id },
+						chain_optional_iter(
+							[],
+							[
+								option_sync_entry!(r.name, indexer_rule::name),
+								option_sync_entry!(r.default, indexer_rule::default),
+								option_sync_entry!(r.rules_per_kind, indexer_rule::rules_per_kind),
+								option_sync_entry!(r.date_created, indexer_rule::date_created),
+								option_sync_entry!(r.date_modified, indexer_rule::date_modified),
+							],
+						),
+					)
+				})
+				.collect()
+		},
+	)
+}
+
+/// Only the rules of the device's locations, as other locations aren't backfilled by it.
+fn paginate_indexer_rules_in_location<'a>(
+	ctx: &'a BackfillContext<'a>,
+) -> impl Stream<Item = Result<Page, Error>> + Send + 'a {
+	let BackfillContext {
+		db,
+		sync,
+		device_ids,
+		..
+	} = *ctx;
+
+	paginate_relation(
+		ctx,
+		BackfillEntity::IndexerRulesInLocation,
+		move |group_id, item_id, take| {
+			db.indexer_rules_in_location()
+				.find_many(vec![
+					or![
+						indexer_rules_in_location::location_id::gt(group_id),
+						and![
+							indexer_rules_in_location::location_id::equals(group_id),
+							indexer_rules_in_location::indexer_rule_id::gt(item_id)
+						]
+					],
+					indexer_rules_in_location::location::is(vec![location::device_id::in_vec(
+						owned_by(device_ids),
+					)]),
+				])
+				.order_by(indexer_rules_in_location::location_id::order(
+					SortOrder::Asc,
+				))
+				.order_by(indexer_rules_in_location::indexer_rule_id::order(
+					SortOrder::Asc,
+				))
+				.take(take)
+				.include(indexer_rules_in_location::include!({
+					location: select { pub_id }
+					indexer_rule: select { pub_id }
+				}))
+				.exec()
+		},
+		|r_l| (r_l.location_id, r_l.indexer_rule_id),
+		move |rules_in_locations| {
+			rules_in_locations
+				.into_iter()
+				.map(|r_l| {
+					sync.relation_create(
+						prisma_sync::indexer_rules_in_location::SyncId {
+							location: prisma_sync::location::SyncId {
+								pub_id: r_l.location.pub_id,
+							},
+							indexer_rule: prisma_sync::indexer_rule::SyncId {
+								pub_id: r_l.indexer_rule.pub_id,
+							},
+						},
+						[],
+					)
+				})
+				.collect()
+		},
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::{cell::RefCell, sync::Arc, task::Poll};
+
+	use futures::future;
+
+	#[test]
+	fn plan_schedules_dependencies_first() {
+		let plan = BackfillPlan::default();
+
+		let stage_of = |entity| {
+			plan.stages()
+				.iter()
+				.position(|stage| stage.contains(&entity))
+				.expect("every entity is scheduled")
+		};
+
+		for entity in BackfillEntity::ALL {
+			for &dependency in entity.dependencies() {
+				assert!(
+					stage_of(dependency) < stage_of(entity),
+					"{entity:?} scheduled before its dependency {dependency:?}"
+				);
+			}
+		}
+
+		assert_eq!(plan.entities().count(), BackfillEntity::ALL.len());
+	}
+
+	#[test]
+	fn plan_parallelizes_independent_entities() {
+		let plan = BackfillPlan::default();
+
+		assert_eq!(
+			plan.stages()[0],
+			[
+				BackfillEntity::Device,
+				BackfillEntity::Tag,
+				BackfillEntity::Label,
+				BackfillEntity::Album,
+				BackfillEntity::SavedSearch,
+				BackfillEntity::Preference,
+				BackfillEntity::IndexerRule,
+			]
+		);
+	}
+
+	#[test]
+	fn plan_ignores_missing_dependencies() {
+		let plan = BackfillPlan::new([BackfillEntity::TagOnObject, BackfillEntity::Tag]);
+
+		assert_eq!(
+			plan.stages(),
+			[vec![BackfillEntity::Tag], vec![BackfillEntity::TagOnObject]]
+		);
+	}
+
+	#[test]
+	fn every_synced_model_is_backfilled() {
+		let schema = include_str!("../../../prisma/schema.prisma");
+
+		let synced_models = schema
+			.lines()
+			.zip(schema.lines().skip(1))
+			.filter(|(attribute, _)| {
+				attribute.starts_with("/// @shared(") || attribute.starts_with("/// @relation(")
+			})
+			.filter_map(|(_, model)| model.strip_prefix("model "))
+			.filter_map(|model| model.split_whitespace().next())
+			.collect::<BTreeSet<_>>();
+
+		let backfilled_models = BackfillEntity::ALL
+			.iter()
+			.map(|entity| entity.name())
+			.collect::<BTreeSet<_>>();
+
+		assert_eq!(
+			backfilled_models.len(),
+			BackfillEntity::ALL.len(),
+			"duplicated entity in the registry"
+		);
+		assert_eq!(synced_models, backfilled_models);
+	}
+
+	#[test]
+	fn skips_repeated_relations() {
+		let op = |record_id: &str| CRDTOperation {
+			device_pub_id: uuid::Uuid::nil(),
+			timestamp: uhlc::NTP64(0),
+			model_id: prisma_sync::tag_on_object::MODEL_ID,
+			record_id: rmpv::Value::from(record_id),
+			data: sd_sync::CRDTOperationData::Create(BTreeMap::new()),
+		};
+
+		let entity = BackfillEntity::TagOnObject;
+		let mut seen_records = HashSet::new();
+
+		let mut operations = vec![op("a"), op("b"), op("a")];
+		skip_seen_records(entity, &mut operations, &mut seen_records).unwrap();
+		assert_eq!(operations.len(), 2);
+
+		let mut operations = vec![op("b"), op("c")];
+		skip_seen_records(entity, &mut operations, &mut seen_records).unwrap();
+		assert_eq!(operations.len(), 1);
+		assert_eq!(operations[0].record_id, rmpv::Value::from("c"));
+	}
+
+	#[test]
+	fn scheduler_starts_entities_once_their_dependencies_complete() {
+		let started = RefCell::new(Vec::new());
+		let completed = RefCell::new(Vec::new());
+
+		let operations =
+			futures::executor::block_on(schedule_entities(&BackfillPlan::default(), |entity| {
+				for &dependency in entity.dependencies() {
+					assert!(
+						completed.borrow().contains(&dependency),
+						"{entity:?} started before its dependency {dependency:?} completed"
+					);
+				}
+
+				started.borrow_mut().push(entity);
+
+				let (started, completed) = (&started, &completed);
+				async move {
+					// Nothing depends on preferences, so they mustn't hold back file paths,
+					// which aren't in the first stage
+					if entity == BackfillEntity::Preference {
+						for _ in 0..100 {
+							if started.borrow().contains(&BackfillEntity::FilePath) {
+								break;
+							}
+
+							yield_now().await;
+						}
+					}
+
+					completed.borrow_mut().push(entity);
+
+					Ok(1)
+				}
+			}))
+			.unwrap();
+
+		assert_eq!(operations.len(), BackfillEntity::ALL.len());
+		assert_eq!(completed.borrow().last(), Some(&BackfillEntity::Preference));
+	}
+
+	#[test]
+	fn scheduler_stops_at_first_error() {
+		let res = futures::executor::block_on(schedule_entities(
+			&BackfillPlan::default(),
+			|entity| async move {
+				assert_ne!(
+					entity,
+					BackfillEntity::Volume,
+					"started a dependent of a failure"
+				);
+
+				if entity == BackfillEntity::Device {
+					Err(Error::Cancelled)
+				} else {
+					Ok(1)
+				}
+			},
+		));
+
+		assert!(matches!(res, Err(Error::Cancelled)));
+	}
+
+	/// Lets the other futures of the scheduler make progress, like `tokio::task::yield_now`.
+	async fn yield_now() {
+		let mut yielded = false;
+
+		future::poll_fn(|cx| {
+			if yielded {
+				Poll::Ready(())
+			} else {
+				yielded = true;
+				cx.waker().wake_by_ref();
+				Poll::Pending
+			}
+		})
+		.await;
+	}
+
+	/// A sync manager for the local device of a new database.
+	async fn test_sync() -> SyncManager {
+		let db = sd_prisma::test_db().await;
+		db._db_push().await.unwrap();
+
+		let device_pub_id = DevicePubId::new();
+		db.device()
+			.create(device_pub_id.to_db(), vec![])
+			.exec()
+			.await
+			.unwrap();
+
+		SyncManager::new(db, &device_pub_id, Arc::default())
+			.await
+			.unwrap()
+			.0
+	}
+
+	async fn count_operations(sync: &SyncManager, entity: BackfillEntity) -> i64 {
+		sync.db
+			.crdt_operation()
+			.count(vec![crdt_operation::model::equals(i32::from(
+				entity.model_id(),
+			))])
+			.exec()
+			.await
+			.unwrap()
+	}
+
+	#[tokio::test]
+	async fn resumes_interrupted_backfill_from_checkpoint() {
+		let sync = test_sync().await;
+
+		let mut tags = vec![];
+		for _ in 0..3 {
+			tags.push(
+				sync.db
+					.tag()
+					.create(DevicePubId::new().to_db(), vec![])
+					.exec()
+					.await
+					.unwrap(),
+			);
+		}
+
+		// Committing every page with its checkpoint, so the first one survives the interruption
+		let config = BackfillConfig {
+			page_size: 1,
+			chunking: Some(BackfillChunking::Pages(1)),
+			..Default::default()
+		};
+
+		// Interrupts the backfill right after the first page of tags
+		let cancellation_token = CancellationToken::new();
+		let res = backfill_operations_cancellable(
+			&sync,
+			&config,
+			|progress| {
+				if progress.entity == BackfillEntity::Tag && progress.processed == 1 {
+					cancellation_token.cancel();
+				}
+			},
+			&cancellation_token,
+		)
+		.await;
+
+		assert!(matches!(res, Err(Error::Cancelled)));
+
+		let checkpoint = sync
+			.db
+			.backfill_checkpoint()
+			.find_unique(backfill_checkpoint::device_pub_id_entity(
+				sync.device_pub_id.to_db(),
+				BackfillEntity::Tag.name().to_string(),
+			))
+			.exec()
+			.await
+			.unwrap()
+			.expect("the first page of tags was committed with its checkpoint");
+
+		assert_eq!(checkpoint.cursor, tags[0].id);
+		assert_eq!(count_operations(&sync, BackfillEntity::Tag).await, 1);
+
+		let report = backfill_operations_with_progress(&sync, &config, |_| {})
+			.await
+			.unwrap();
+
+		assert_eq!(report.operations[&BackfillEntity::Tag], 2);
+		assert_eq!(count_operations(&sync, BackfillEntity::Tag).await, 3);
+		assert_eq!(count_operations(&sync, BackfillEntity::Device).await, 1);
+		assert_eq!(
+			sync.db
+				.backfill_checkpoint()
+				.count(vec![])
+				.exec()
+				.await
+				.unwrap(),
+			0
+		);
+	}
+
+	#[tokio::test]
+	async fn parallel_pages_resume_without_gaps_or_duplicates() {
+		const ROWS: i32 = 6;
+
+		let sync = test_sync().await;
+		let local_device = sync
+			.db
+			.device()
+			.find_unique(device::pub_id::equals(sync.device_pub_id.to_db()))
+			.exec()
+			.await
+			.unwrap()
+			.unwrap();
+
+		let config = BackfillConfig {
+			page_size: 1,
+			parallel_pages: usize::try_from(ROWS).unwrap(),
+			..Default::default()
+		};
+
+		let operation = |row: i32| {
+			sync.shared_create(
+				prisma_sync::object::SyncId {
+					pub_id: row.to_le_bytes().to_vec(),
+				},
+				[],
+			)
+		};
+
+		// Each range holds a single row, and the first ranges complete last
+		let backfill = |checkpoints: HashMap<String, backfill_checkpoint::Data>,
+		                pages_count: usize| {
+			let (sync, local_device, config, operation) =
+				(&sync, &local_device, &config, &operation);
+
+			async move {
+				let ctx = BackfillContext {
+					db: &sync.db,
+					sync,
+					local_device,
+					device_id: local_device.id,
+					device_ids: &[local_device.id],
+					config,
+					totals: &HashMap::new(),
+					checkpoints: &checkpoints,
+					on_progress: &|_| {},
+					cancellation_token: &CancellationToken::new(),
+					dry_run: false,
+					save_checkpoints: true,
+				};
+
+				paginate_parallel(
+					&ctx,
+					BackfillEntity::Object,
+					async { Ok::<_, Error>(Some(ROWS)) },
+					|after, take| async move {
+						sleep(Duration::from_millis(
+							10 * u64::try_from(ROWS - after).unwrap(),
+						))
+						.await;
+
+						Ok::<_, Error>(
+							(after + 1..=ROWS)
+								.take(usize::try_from(take).unwrap())
+								.collect::<Vec<_>>(),
+						)
+					},
+					|row| *row,
+					|rows| rows.into_iter().map(operation).collect(),
+				)
+				.take(pages_count)
+				.try_collect::<Vec<_>>()
+				.await
+				.unwrap()
+			}
+		};
+
+		// Interrupted after two pages, while the later ones were already fetched
+		let written = backfill(HashMap::new(), 2).await;
+		let (cursor, item_cursor) = written.last().and_then(|page| page.next_cursor).unwrap();
+
+		assert_eq!(cursor, 2);
+
+		let checkpoint = backfill_checkpoint::Data {
+			device_pub_id: sync.device_pub_id.to_db(),
+			entity: BackfillEntity::Object.name().to_string(),
+			cursor,
+			item_cursor,
+		};
+		let resumed = backfill(
+			HashMap::from([(checkpoint.entity.clone(), checkpoint)]),
+			usize::MAX,
+		)
+		.await;
+
+		assert_eq!(
+			written
+				.into_iter()
+				.chain(resumed)
+				.flat_map(|page| page.operations)
+				.map(|op| op.record_id)
+				.collect::<Vec<_>>(),
+			(1..=ROWS)
+				.map(|row| operation(row).record_id)
+				.collect::<Vec<_>>()
+		);
+	}
+
+	#[tokio::test]
+	async fn since_does_not_duplicate_undated_entities() {
+		let sync = test_sync().await;
+
+		backfill_operations(&sync).await.unwrap();
+		assert_eq!(count_operations(&sync, BackfillEntity::Device).await, 1);
+
+		// Devices have no modification date, so they can't be filtered
+		let config = BackfillConfig {
+			since: Some(Utc::now()),
+			..Default::default()
+		};
+		let report = backfill_operations_with_progress(&sync, &config, |_| {})
+			.await
+			.unwrap();
+
+		assert_eq!(report.operations[&BackfillEntity::Device], 0);
+		assert_eq!(count_operations(&sync, BackfillEntity::Device).await, 1);
+	}
+
+	#[tokio::test]
+	async fn backfill_entity_leaves_no_checkpoints() {
+		let sync = test_sync().await;
+		sync.db
+			.tag()
+			.create(DevicePubId::new().to_db(), vec![])
+			.exec()
+			.await
+			.unwrap();
+
+		backfill_operations(&sync).await.unwrap();
+		assert_eq!(
+			backfill_entity(&sync, BackfillEntity::Tag).await.unwrap(),
+			1
+		);
+
+		assert_eq!(
+			sync.db
+				.backfill_checkpoint()
+				.count(vec![])
+				.exec()
+				.await
+				.unwrap(),
+			0
+		);
+
+		// Not mistaken for an interrupted backfill to resume
+		assert!(matches!(
+			backfill_operations(&sync).await,
+			Err(Error::AlreadyBackfilled)
+		));
+		assert_eq!(count_operations(&sync, BackfillEntity::Device).await, 1);
+		assert_eq!(count_operations(&sync, BackfillEntity::Tag).await, 1);
+	}
 }
@@ -79,6 +79,25 @@ pub enum Error {
 	DeviceNotFound(DevicePubId),
 	#[error("processes crdt task panicked")]
 	ProcessCrdtPanic(JoinError),
+	#[error("backfill was cancelled")]
+	Cancelled,
+	#[error("operations were already backfilled for this device")]
+	AlreadyBackfilled,
+	#[error("backfill failed after {attempts} attempts: {source}")]
+	BackfillRetryExhausted { attempts: u32, source: Box<Error> },
+	#[error("volume <pub_id={0}> has no device")]
+	OrphanVolume(uuid::Uuid),
+	#[error("backfill cursor of {entity} didn't move forward, from {previous:?} to {next:?}")]
+	NonMonotonicCursor {
+		entity: &'static str,
+		previous: (i32, Option<i32>),
+		next: (i32, Option<i32>),
+	},
+	#[error("backfill of {entity} found more than one record with sync id {record_id}")]
+	DuplicateSyncId {
+		entity: &'static str,
+		record_id: String,
+	},
 }
 
 impl From<Error> for rspc::Error {
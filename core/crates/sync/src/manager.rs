@@ -377,6 +377,10 @@ impl Manager {
 			self.ingest_by_model(prisma_sync::location::MODEL_ID),
 			self.ingest_by_model(prisma_sync::object::MODEL_ID),
 			self.ingest_by_model(prisma_sync::label::MODEL_ID),
+			self.ingest_by_model(prisma_sync::album::MODEL_ID),
+			self.ingest_by_model(prisma_sync::saved_search::MODEL_ID),
+			self.ingest_by_model(prisma_sync::preference::MODEL_ID),
+			self.ingest_by_model(prisma_sync::indexer_rule::MODEL_ID),
 		]
 		.try_join()
 		.await?
@@ -388,6 +392,8 @@ impl Manager {
 			self.ingest_by_model(prisma_sync::file_path::MODEL_ID),
 			self.ingest_by_model(prisma_sync::tag_on_object::MODEL_ID),
 			self.ingest_by_model(prisma_sync::label_on_object::MODEL_ID),
+			self.ingest_by_model(prisma_sync::object_in_album::MODEL_ID),
+			self.ingest_by_model(prisma_sync::indexer_rules_in_location::MODEL_ID),
 		]
 		.try_join()
 		.await?
@@ -748,3 +754,74 @@ impl OperationFactory for Manager {
 		sd_sync::DevicePubId::from(&self.device_pub_id)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::cloud_crdt_op_db;
+
+	#[tokio::test]
+	async fn ingest_indexer_rules() {
+		let db = sd_prisma::test_db().await;
+		db._db_push().await.unwrap();
+
+		let (sync, _) = Manager::new(Arc::clone(&db), &DevicePubId::new(), Arc::default())
+			.await
+			.unwrap();
+		let (remote, _) = Manager::new(Arc::clone(&db), &DevicePubId::new(), Arc::default())
+			.await
+			.unwrap();
+
+		let (location_pub_id, indexer_rule_pub_id) =
+			(DevicePubId::new().to_db(), DevicePubId::new().to_db());
+
+		// The location only exists so the relation has something to point to
+		let ops = [
+			remote.shared_create(
+				prisma_sync::location::SyncId {
+					pub_id: location_pub_id.clone(),
+				},
+				[],
+			),
+			remote.shared_create(
+				prisma_sync::indexer_rule::SyncId {
+					pub_id: indexer_rule_pub_id.clone(),
+				},
+				[],
+			),
+			remote.relation_create(
+				prisma_sync::indexer_rules_in_location::SyncId {
+					location: prisma_sync::location::SyncId {
+						pub_id: location_pub_id,
+					},
+					indexer_rule: prisma_sync::indexer_rule::SyncId {
+						pub_id: indexer_rule_pub_id,
+					},
+				},
+				[],
+			),
+		];
+
+		db._batch(
+			ops.iter()
+				.map(|op| cloud_crdt_op_db(op).map(|op| op.to_query(&db)))
+				.collect::<Result<Vec<_>, _>>()
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(sync.ingest_ops().await.unwrap(), ops.len());
+
+		assert_eq!(db.indexer_rule().count(vec![]).exec().await.unwrap(), 1);
+		assert_eq!(
+			db.indexer_rules_in_location()
+				.count(vec![])
+				.exec()
+				.await
+				.unwrap(),
+			1
+		);
+	}
+}
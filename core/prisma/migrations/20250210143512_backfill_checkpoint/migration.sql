@@ -0,0 +1,9 @@
+-- CreateTable
+CREATE TABLE "backfill_checkpoint" (
+    "device_pub_id" BLOB NOT NULL,
+    "entity" TEXT NOT NULL,
+    "cursor" INTEGER NOT NULL,
+    "item_cursor" INTEGER,
+
+    PRIMARY KEY ("device_pub_id", "entity")
+);
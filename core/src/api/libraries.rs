@@ -449,6 +449,8 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			pub struct EditLibraryArgs {
 				pub id: Uuid,
 				pub name: Option<LibraryName>,
+				#[serde(default)]
+				#[specta(optional)]
 				pub description: MaybeUndefined<String>,
 			}
 
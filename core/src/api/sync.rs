@@ -19,7 +19,11 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						return Ok(());
 					}
 
-					sd_core_sync::backfill::backfill_operations(&library.sync).await?;
+					match sd_core_sync::backfill::backfill_operations(&library.sync).await {
+						// A previous call already backfilled but didn't get to enable sync
+						Ok(_) | Err(sd_core_sync::Error::AlreadyBackfilled) => {}
+						Err(e) => return Err(e.into()),
+					}
 
 					node.libraries
 						.edit(
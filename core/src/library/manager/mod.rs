@@ -685,6 +685,24 @@ async fn special_sync_indexes(db: &PrismaClient) -> Result<(), LibraryManagerErr
 			prisma_sync::label_on_object::MODEL_ID,
 			prisma::label_on_object::NAME,
 		),
+		(prisma_sync::album::MODEL_ID, prisma::album::NAME),
+		(
+			prisma_sync::saved_search::MODEL_ID,
+			prisma::saved_search::NAME,
+		),
+		(prisma_sync::preference::MODEL_ID, prisma::preference::NAME),
+		(
+			prisma_sync::object_in_album::MODEL_ID,
+			prisma::object_in_album::NAME,
+		),
+		(
+			prisma_sync::indexer_rule::MODEL_ID,
+			prisma::indexer_rule::NAME,
+		),
+		(
+			prisma_sync::indexer_rules_in_location::MODEL_ID,
+			prisma::indexer_rules_in_location::NAME,
+		),
 	] {
 		// Creating indexes sequentially just in case
 		create_index(db, model_id, model_name).await?;
@@ -4,7 +4,8 @@ use crate::{
 	location::{
 		create_file_path, delete_directory, find_location,
 		indexer::reverse_update_directories_sizes, location_with_indexer_rules,
-		manager::LocationManagerError, scan_location_sub_path, update_location_size,
+		manager::LocationManagerError, metadata::is_metadata_file_name, scan_location_sub_path,
+		update_location_size,
 	},
 	object::validation::hash::file_checksum,
 	Node,
@@ -80,12 +81,12 @@ pub(super) async fn reject_event(
 	location_path: Option<&Path>,
 	indexer_ruler: Option<&IndexerRuler>,
 ) -> bool {
-	// if path includes .DS_Store, .spacedrive file creation or is in the `ignore_paths` set, we ignore
+	// if path includes .DS_Store, .spacedrive file (or its sidecars) creation or is in the
+	// `ignore_paths` set, we ignore
 	if event.paths.iter().any(|p| {
-		p.file_name()
-			.and_then(OsStr::to_str)
-			.map_or(false, |name| name == ".DS_Store" || name == ".spacedrive")
-			|| ignore_paths.contains(p)
+		p.file_name().and_then(OsStr::to_str).map_or(false, |name| {
+			name == ".DS_Store" || is_metadata_file_name(name)
+		}) || ignore_paths.contains(p)
 	}) {
 		trace!("Rejected by ignored paths");
 		return true;
@@ -1,25 +1,72 @@
 use crate::library::LibraryId;
 
+use sd_crypto::{
+	cloud::{decrypt::OneShotDecryption, encrypt::OneShotEncryption, secret_key::SecretKey},
+	primitives::{EncryptedBlock, EncryptedBlockRef, OneShotNonce},
+	CryptoRng, Error as CryptoError, Protected,
+};
+
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{hash_map::Entry, HashMap, HashSet},
+	ffi::OsString,
+	mem,
 	path::{Path, PathBuf},
+	sync::{Arc, LazyLock, Mutex as StdMutex, PoisonError, Weak},
+	time::Duration,
 };
 
+use async_channel as chan;
+use async_stream::try_stream;
 use chrono::{DateTime, Utc};
+use futures::Stream;
+use mini_moka::sync::Cache;
+use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::{
 	fs::{self, OpenOptions},
 	io::{self, AsyncWriteExt},
+	sync::{Mutex, OwnedMutexGuard},
 };
-use tracing::error;
+use tracing::{debug, error, warn};
 use uuid::Uuid;
 
 use super::LocationPubId;
 
 static SPACEDRIVE_LOCATION_METADATA_FILE: &str = ".spacedrive";
+static TEMPORARY_FILE_SUFFIX: &str = ".tmp";
+static CORRUPTED_FILE_SUFFIX: &str = ".corrupt-";
+static BACKUP_FILE_SUFFIX: &str = ".bak";
+
+/// Whether `file_name` is a location metadata file or one of its sidecars, like its backup, which
+/// must never be indexed nor watched as user files.
+#[must_use]
+pub fn is_metadata_file_name(file_name: &str) -> bool {
+	file_name
+		.strip_prefix(SPACEDRIVE_LOCATION_METADATA_FILE)
+		.is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Context for deriving the key encrypting metadata files from the key material given to
+/// [`SpacedriveLocationMetadataFile::try_load_encrypted`] and
+/// [`SpacedriveLocationMetadataFile::create_and_save_encrypted`].
+static ENCRYPTION_KEY_CONTEXT: &str = "spacedrive 2024-11-01 location metadata encryption key";
+
+/// Version of the metadata file layout, to be bumped on every change to
+/// [`SpacedriveLocationMetadata`] along with a new step in
+/// [`SpacedriveLocationMetadataFile::migrate`].
+const METADATA_VERSION: u32 = 1;
+
+/// In-process locks of the metadata files, keyed by their canonical path.
+static METADATA_FILE_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+	LazyLock::new(Mutex::default);
+
+/// Every [`MetadataCache`] alive in this process, so a write through any metadata file drops its
+/// stale entry from all of them.
+static METADATA_CACHES: LazyLock<StdMutex<Vec<Weak<CachedMetadata>>>> =
+	LazyLock::new(StdMutex::default);
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 struct LocationMetadata {
 	pub_id: LocationPubId,
 	name: String,
@@ -28,96 +75,455 @@ struct LocationMetadata {
 	updated_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+/// State of the metadata file of a location, found by [`SpacedriveLocationMetadataFile::inspect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataStatus {
+	Missing,
+	/// Registered by this many libraries
+	Valid(usize),
+	/// Encrypted, so it can't be inspected without its key
+	Encrypted,
+	Corrupt,
+}
+
+/// Read-only snapshot of the metadata of a location for a library, returned by
+/// [`SpacedriveLocationMetadataFile::metadata_for`].
+#[derive(Debug, Clone)]
+pub struct LocationMetadataView {
+	pub pub_id: LocationPubId,
+	pub name: String,
+	pub path: PathBuf,
+	/// When the library registered the location.
+	pub created_at: DateTime<Utc>,
+	pub updated_at: DateTime<Utc>,
+}
+
+impl From<&LocationMetadata> for LocationMetadataView {
+	fn from(metadata: &LocationMetadata) -> Self {
+		Self {
+			pub_id: metadata.pub_id,
+			name: metadata.name.clone(),
+			path: metadata.path.clone(),
+			created_at: metadata.created_at,
+			updated_at: metadata.updated_at,
+		}
+	}
+}
+
+/// Identity of a location for a library, returned by
+/// [`SpacedriveLocationMetadataFile::fingerprint`], to detect the same location being added
+/// twice, like through a symlink or another mount point.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LocationFingerprint {
+	pub pub_id: LocationPubId,
+	/// Path recorded for the location, normalized for comparisons
+	pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 struct SpacedriveLocationMetadata {
+	/// Files written before versioning have no version, deserialized as 0
+	#[serde(default)]
+	version: u32,
 	libraries: HashMap<LibraryId, LocationMetadata>,
 	created_at: DateTime<Utc>,
 	updated_at: DateTime<Utc>,
 }
 
+/// Metadata along with a checksum of its serialization, to detect silent corruption like bad
+/// sectors, as written on disk.
+#[derive(Serialize, Deserialize)]
+struct ChecksummedMetadata {
+	checksum: String,
+	data: serde_json::Value,
+}
+
+/// Nonce and cipher text of the checksummed metadata, hex encoded.
+#[derive(Serialize, Deserialize)]
+struct EncryptedMetadata {
+	encrypted: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MetadataFileContents {
+	Checksummed(ChecksummedMetadata),
+	Encrypted(EncryptedMetadata),
+	/// Files written before checksums were added only have the bare metadata
+	Unchecked(SpacedriveLocationMetadata),
+}
+
+/// The `.spacedrive` file at the root of a location, mapping each library using the location to
+/// its pub_id there.
+///
+/// Every change is made while holding an in-process lock on the file, after re-reading it from
+/// disk, so concurrent changes from different instances loaded from the same file, like adding
+/// two libraries, don't clobber each other. The lock doesn't protect against other processes,
+/// like another Spacedrive instance sharing the drive.
+///
+/// For locations on shared or cloud drives, the file can be encrypted at rest, so it doesn't leak
+/// the ids of the libraries nor the names and paths of the location. Plain text is the default.
 pub struct SpacedriveLocationMetadataFile {
 	path: PathBuf,
+	file_name: String,
 	metadata: SpacedriveLocationMetadata,
+	encryption_key: Option<SecretKey>,
+	durable_writes: bool,
+	/// Cache this file was loaded from, kept up to date with our writes
+	cache: Option<CachedMetadata>,
+}
+
+/// Loads or creates a [`SpacedriveLocationMetadataFile`] with a custom file name, for embedders
+/// that want to avoid clashes with `.spacedrive`, or encrypted.
+#[derive(Debug, Clone)]
+pub struct LocationMetadataFileBuilder {
+	file_name: String,
+	encryption_key: Option<SecretKey>,
+	durable_writes: bool,
+}
+
+impl Default for LocationMetadataFileBuilder {
+	fn default() -> Self {
+		Self {
+			file_name: SPACEDRIVE_LOCATION_METADATA_FILE.to_string(),
+			encryption_key: None,
+			durable_writes: true,
+		}
+	}
+}
+
+impl LocationMetadataFileBuilder {
+	/// Uses `file_name` instead of `.spacedrive`. It must keep the `.spacedrive.` prefix, like
+	/// `.spacedrive.my-app`, so the indexer and the location watcher keep ignoring the file and
+	/// its sidecars, see [`is_metadata_file_name`].
+	///
+	/// # Panics
+	/// Panics if `file_name` doesn't start with the reserved `.spacedrive.` prefix.
+	#[must_use]
+	pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+		let file_name = file_name.into();
+
+		assert!(
+			is_metadata_file_name(&file_name),
+			"location metadata file name must start with `{SPACEDRIVE_LOCATION_METADATA_FILE}.`, \
+			got `{file_name}`",
+		);
+
+		self.file_name = file_name;
+		self
+	}
+
+	/// Encrypts the metadata file with a key derived from `key_material`, like a library secret.
+	///
+	/// Files written in plain text are still loaded, being encrypted on their next write.
+	#[must_use]
+	pub fn encryption_key(mut self, key_material: &Protected<Vec<u8>>) -> Self {
+		self.encryption_key = Some(derive_encryption_key(key_material));
+		self
+	}
+
+	/// See [`SpacedriveLocationMetadataFile::set_durable_writes`], enabled by default.
+	#[must_use]
+	pub fn durable_writes(mut self, durable_writes: bool) -> Self {
+		self.durable_writes = durable_writes;
+		self
+	}
+
+	pub async fn try_load(
+		self,
+		location_path: impl AsRef<Path>,
+	) -> Result<Option<SpacedriveLocationMetadataFile>, LocationMetadataError> {
+		SpacedriveLocationMetadataFile::load(location_path.as_ref(), self).await
+	}
+
+	pub async fn inspect(
+		self,
+		location_path: impl AsRef<Path>,
+	) -> Result<MetadataStatus, LocationMetadataError> {
+		SpacedriveLocationMetadataFile::inspect_file(location_path.as_ref(), &self.file_name).await
+	}
+
+	/// Registers the location of `library_id`, adding it to the existing metadata file if the
+	/// location is shared with other libraries, instead of replacing their entries.
+	pub async fn create_and_save(
+		self,
+		library_id: LibraryId,
+		location_pub_id: Uuid,
+		location_path: impl AsRef<Path>,
+		location_name: String,
+	) -> Result<(), LocationMetadataError> {
+		SpacedriveLocationMetadataFile::create(
+			library_id,
+			location_pub_id,
+			location_path.as_ref(),
+			location_name,
+			self,
+		)
+		.await
+	}
+
+	/// See [`SpacedriveLocationMetadataFile::clean_stale_libraries_recursive`].
+	pub async fn clean_stale_libraries_recursive(
+		self,
+		root: impl AsRef<Path>,
+		existing_libraries_ids: &HashSet<LibraryId>,
+	) -> Result<Vec<(PathBuf, LibraryId)>, LocationMetadataError> {
+		let root = root.as_ref();
+		let mut removed = vec![];
+		let mut pending_dirs = vec![root.to_path_buf()];
+
+		while let Some(dir) = pending_dirs.pop() {
+			match self.clone().try_load(&dir).await {
+				Ok(Some(mut metadata_file)) => removed.extend(
+					metadata_file
+						.clean_stale_libraries(existing_libraries_ids)
+						.await?
+						.into_iter()
+						.map(|library_id| (dir.clone(), library_id)),
+				),
+				Ok(None) => {}
+				Err(e) if dir == root => return Err(e),
+				Err(e) => {
+					// A single encrypted or unreadable file shouldn't stop cleaning the other ones
+					warn!(dir = %dir.display(), ?e, "Skipping unreadable location metadata file;");
+				}
+			}
+
+			let mut entries = match fs::read_dir(&dir).await {
+				Ok(entries) => entries,
+				Err(e) if dir == root => return Err(LocationMetadataError::Read(e, dir)),
+				Err(e) => {
+					// Directories we can't list, like system ones, can't hold our locations anyway
+					warn!(dir = %dir.display(), ?e, "Skipping unreadable directory;");
+					continue;
+				}
+			};
+
+			while let Some(entry) = entries
+				.next_entry()
+				.await
+				.map_err(|e| LocationMetadataError::Read(e, dir.clone()))?
+			{
+				// Symlinks aren't followed, to avoid cycles and visiting the same file twice
+				if entry
+					.file_type()
+					.await
+					.map_err(|e| LocationMetadataError::Read(e, entry.path()))?
+					.is_dir()
+				{
+					pending_dirs.push(entry.path());
+				}
+			}
+		}
+
+		Ok(removed)
+	}
 }
 
 impl SpacedriveLocationMetadataFile {
+	#[must_use]
+	pub fn builder() -> LocationMetadataFileBuilder {
+		LocationMetadataFileBuilder::default()
+	}
+
 	pub async fn try_load(
 		location_path: impl AsRef<Path>,
 	) -> Result<Option<Self>, LocationMetadataError> {
-		let metadata_file_name = location_path
-			.as_ref()
-			.join(SPACEDRIVE_LOCATION_METADATA_FILE);
-
-		match fs::read(&metadata_file_name).await {
-			Ok(data) => Ok(Some(Self {
-				metadata: match serde_json::from_slice(&data) {
-					Ok(data) => data,
-					Err(e) => {
-						#[cfg(debug_assertions)]
-						{
-							error!(
-								metadata_file_name = %metadata_file_name.display(),
-								?e,
-								"Failed to deserialize corrupted metadata file, \
-								we will remove it and create a new one;",
-							);
-
-							fs::remove_file(&metadata_file_name).await.map_err(|e| {
-								LocationMetadataError::Delete(
-									e,
-									location_path.as_ref().to_path_buf(),
-								)
-							})?;
-
-							return Ok(None);
-						}
-
-						#[cfg(not(debug_assertions))]
-						return Err(LocationMetadataError::Deserialize(
-							e,
-							location_path.as_ref().to_path_buf(),
-						));
-					}
+		Self::builder().try_load(location_path).await
+	}
+
+	/// Same as [`SpacedriveLocationMetadataFile::try_load`], for a metadata file encrypted with
+	/// a key derived from `key_material`, like a library secret. Keeps writing it encrypted.
+	///
+	/// Files written in plain text are still loaded, being encrypted on their next write.
+	pub async fn try_load_encrypted(
+		location_path: impl AsRef<Path>,
+		key_material: &Protected<Vec<u8>>,
+	) -> Result<Option<Self>, LocationMetadataError> {
+		Self::builder()
+			.encryption_key(key_material)
+			.try_load(location_path)
+			.await
+	}
+
+	async fn load(
+		location_path: &Path,
+		LocationMetadataFileBuilder {
+			file_name,
+			encryption_key,
+			durable_writes,
+		}: LocationMetadataFileBuilder,
+	) -> Result<Option<Self>, LocationMetadataError> {
+		let metadata_file_name = location_path.join(&file_name);
+
+		let data = match fs::read(&metadata_file_name).await {
+			Ok(data) => data,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(LocationMetadataError::Read(e, location_path.to_path_buf())),
+		};
+
+		let mut metadata_file =
+			match decode_metadata(&data, &metadata_file_name, encryption_key.as_ref()) {
+				Ok(metadata) => Self {
+					path: metadata_file_name,
+					file_name,
+					metadata,
+					encryption_key,
+					durable_writes,
+					cache: None,
 				},
-				path: metadata_file_name,
-			})),
-			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
-			Err(e) => Err(LocationMetadataError::Read(
-				e,
-				location_path.as_ref().to_path_buf(),
-			)),
-		}
+				Err(
+					e @ (LocationMetadataError::Deserialize(..)
+					| LocationMetadataError::ChecksumMismatch(_)),
+				) => {
+					// Losing the whole location on a single bad byte is worse than having to
+					// re-register it, so we move the corrupted file aside, keeping it around for a
+					// manual recovery, and fall back to the backup of the previous version, if any
+					let corrupted_file_name = with_suffix(
+						&metadata_file_name,
+						&format!("{CORRUPTED_FILE_SUFFIX}{}", Utc::now().timestamp()),
+					);
+
+					error!(
+						metadata_file_name = %metadata_file_name.display(),
+						corrupted_file_name = %corrupted_file_name.display(),
+						?e,
+						"Failed to deserialize corrupted metadata file, we will move it aside;",
+					);
+
+					fs::rename(&metadata_file_name, &corrupted_file_name)
+						.await
+						.map_err(|e| {
+							LocationMetadataError::MoveCorrupted(e, location_path.to_path_buf())
+						})?;
+
+					let Some(metadata) =
+						read_backup(&metadata_file_name, encryption_key.as_ref()).await
+					else {
+						return Ok(None);
+					};
+
+					let metadata_file = Self {
+						path: metadata_file_name,
+						file_name,
+						metadata,
+						encryption_key,
+						durable_writes,
+						cache: None,
+					};
+
+					let _lock = lock_metadata_file(&metadata_file.path).await;
+					metadata_file.write_metadata().await?;
+
+					metadata_file
+				}
+				// A missing or wrong key isn't corruption, so we leave the file alone
+				Err(e) => return Err(e),
+			};
+
+		metadata_file.migrate().await?;
+
+		Ok(Some(metadata_file))
+	}
+
+	/// Checks whether `location_path` has a valid metadata file, without any of the recovery
+	/// [`SpacedriveLocationMetadataFile::try_load`] does, so the filesystem is never touched.
+	pub async fn inspect(
+		location_path: impl AsRef<Path>,
+	) -> Result<MetadataStatus, LocationMetadataError> {
+		Self::builder().inspect(location_path).await
 	}
 
+	async fn inspect_file(
+		location_path: &Path,
+		file_name: &str,
+	) -> Result<MetadataStatus, LocationMetadataError> {
+		let metadata_file_name = location_path.join(file_name);
+
+		let data = match fs::read(&metadata_file_name).await {
+			Ok(data) => data,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(MetadataStatus::Missing),
+			Err(e) => return Err(LocationMetadataError::Read(e, location_path.to_path_buf())),
+		};
+
+		Ok(match decode_metadata(&data, &metadata_file_name, None) {
+			Ok(metadata) => MetadataStatus::Valid(metadata.libraries.len()),
+			Err(LocationMetadataError::EncryptionKeyRequired(_)) => MetadataStatus::Encrypted,
+			Err(_) => MetadataStatus::Corrupt,
+		})
+	}
+
+	/// See [`LocationMetadataFileBuilder::create_and_save`].
 	pub async fn create_and_save(
 		library_id: LibraryId,
 		location_pub_id: Uuid,
 		location_path: impl AsRef<Path>,
 		location_name: String,
 	) -> Result<(), LocationMetadataError> {
-		Self {
-			path: location_path
-				.as_ref()
-				.join(SPACEDRIVE_LOCATION_METADATA_FILE),
-			metadata: SpacedriveLocationMetadata {
-				libraries: [(
-					library_id,
-					LocationMetadata {
-						pub_id: location_pub_id,
-						name: location_name,
-						path: location_path.as_ref().to_path_buf(),
+		Self::builder()
+			.create_and_save(library_id, location_pub_id, location_path, location_name)
+			.await
+	}
+
+	/// Same as [`SpacedriveLocationMetadataFile::create_and_save`], but encrypting the metadata
+	/// file with a key derived from `key_material`, like a library secret.
+	pub async fn create_and_save_encrypted(
+		library_id: LibraryId,
+		location_pub_id: Uuid,
+		location_path: impl AsRef<Path>,
+		location_name: String,
+		key_material: &Protected<Vec<u8>>,
+	) -> Result<(), LocationMetadataError> {
+		Self::builder()
+			.encryption_key(key_material)
+			.create_and_save(library_id, location_pub_id, location_path, location_name)
+			.await
+	}
+
+	async fn create(
+		library_id: LibraryId,
+		location_pub_id: Uuid,
+		location_path: &Path,
+		location_name: String,
+		builder: LocationMetadataFileBuilder,
+	) -> Result<(), LocationMetadataError> {
+		// The location may already be registered by other libraries, which must be kept
+		let mut metadata_file = match Self::load(location_path, builder.clone()).await? {
+			Some(metadata_file) => metadata_file,
+			None => {
+				let LocationMetadataFileBuilder {
+					file_name,
+					encryption_key,
+					durable_writes,
+				} = builder;
+
+				Self {
+					path: location_path.join(&file_name),
+					file_name,
+					encryption_key,
+					durable_writes,
+					cache: None,
+					metadata: SpacedriveLocationMetadata {
+						version: METADATA_VERSION,
+						libraries: HashMap::new(),
 						created_at: Utc::now(),
 						updated_at: Utc::now(),
 					},
-				)]
-				.into_iter()
-				.collect(),
-				created_at: Utc::now(),
-				updated_at: Utc::now(),
-			},
-		}
-		.write_metadata()
-		.await
+				}
+			}
+		};
+
+		// Also picks up a file created by someone else since we tried loading it
+		metadata_file
+			.add_library(library_id, location_pub_id, location_path, location_name)
+			.await
+	}
+
+	/// Whether writes wait for the metadata file to hit the disk, so it survives a power loss
+	/// right after them. Enabled by default, it can be disabled for bulk changes, to only sync
+	/// the last one.
+	pub fn set_durable_writes(&mut self, durable_writes: bool) {
+		self.durable_writes = durable_writes;
 	}
 
 	pub async fn relink(
@@ -125,6 +531,11 @@ impl SpacedriveLocationMetadataFile {
 		library_id: LibraryId,
 		location_path: impl AsRef<Path>,
 	) -> Result<(), LocationMetadataError> {
+		let new_metadata_path = location_path.as_ref().join(&self.file_name);
+
+		let _lock = lock_metadata_file(&new_metadata_path).await;
+		self.refresh_from(&new_metadata_path).await?;
+
 		let location_metadata = self
 			.metadata
 			.libraries
@@ -132,33 +543,99 @@ impl SpacedriveLocationMetadataFile {
 			.ok_or(LocationMetadataError::LibraryNotFound(library_id))?;
 
 		let new_path = location_path.as_ref().to_path_buf();
-		if location_metadata.path == new_path {
+		if is_same_path(&location_metadata.path, &new_path) {
 			return Err(LocationMetadataError::RelinkSamePath(new_path));
 		}
 
 		location_metadata.path = new_path;
 		location_metadata.updated_at = Utc::now();
-		self.path = location_path
-			.as_ref()
-			.join(SPACEDRIVE_LOCATION_METADATA_FILE);
+
+		// The file isn't at its old path anymore
+		invalidate_cached(&canonical_file_path(&self.path).await);
+
+		self.path = new_metadata_path;
 
 		self.write_metadata().await
 	}
 
+	/// Whether the location of `library_id` was moved, like an external drive mounted somewhere
+	/// else, as its metadata file was found at `actual_path` but records a different path.
+	///
+	/// Returns `false` if the library didn't register the location.
+	pub fn needs_relink(&self, library_id: LibraryId, actual_path: impl AsRef<Path>) -> bool {
+		self.metadata
+			.libraries
+			.get(&library_id)
+			.is_some_and(|l| !is_same_path(&l.path, actual_path.as_ref()))
+	}
+
+	/// Relinks the location of `library_id` to `actual_parent` if it was moved there, like an
+	/// external drive mounted somewhere else, doing nothing if it's already there. Returns whether
+	/// it was relinked.
+	///
+	/// `actual_parent` is the directory the metadata file was found in, so this is the safe way to
+	/// repair a file whose recorded path disagrees with where it's read from.
+	pub async fn reconcile_self_path(
+		&mut self,
+		library_id: LibraryId,
+		actual_parent: impl AsRef<Path>,
+	) -> Result<bool, LocationMetadataError> {
+		if !self.has_library(library_id) {
+			return Err(LocationMetadataError::LibraryNotFound(library_id));
+		}
+
+		if !self.needs_relink(library_id, &actual_parent) {
+			return Ok(false);
+		}
+
+		match self.relink(library_id, actual_parent).await {
+			Ok(()) => Ok(true),
+			// Someone else relinked it in the meantime
+			Err(LocationMetadataError::RelinkSamePath(_)) => Ok(false),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Renames the location for `library_id`, returning its previous name.
 	pub async fn update(
 		&mut self,
 		library_id: LibraryId,
 		location_name: String,
-	) -> Result<(), LocationMetadataError> {
+	) -> Result<String, LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+		self.refresh().await?;
+
 		let location_metadata = self
 			.metadata
 			.libraries
 			.get_mut(&library_id)
 			.ok_or(LocationMetadataError::LibraryNotFound(library_id))?;
 
-		location_metadata.name = location_name;
+		let previous_name = mem::replace(&mut location_metadata.name, location_name);
 		location_metadata.updated_at = Utc::now();
 
+		self.write_metadata().await?;
+
+		Ok(previous_name)
+	}
+
+	/// Renames the location for every library using it at once, writing the file a single time.
+	pub async fn update_all(&mut self, location_name: String) -> Result<(), LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+		self.refresh().await?;
+
+		if self.metadata.libraries.is_empty() {
+			return Ok(());
+		}
+
+		let now = Utc::now();
+
+		for location_metadata in self.metadata.libraries.values_mut() {
+			location_metadata.name.clone_from(&location_name);
+			location_metadata.updated_at = now;
+		}
+
+		self.metadata.updated_at = now;
 		self.write_metadata().await
 	}
 
@@ -169,6 +646,9 @@ impl SpacedriveLocationMetadataFile {
 		location_path: impl AsRef<Path>,
 		location_name: String,
 	) -> Result<(), LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+		self.refresh().await?;
+
 		self.metadata.libraries.insert(
 			library_id,
 			LocationMetadata {
@@ -184,6 +664,31 @@ impl SpacedriveLocationMetadataFile {
 		self.write_metadata().await
 	}
 
+	/// Merges the libraries of `other`, like the metadata file of the same drive regenerated by
+	/// another instance, keeping the most recently updated entry of the libraries in both.
+	pub async fn merge(&mut self, other: Self) -> Result<(), LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+		self.refresh().await?;
+
+		for (library_id, other_metadata) in other.metadata.libraries {
+			match self.metadata.libraries.entry(library_id) {
+				Entry::Occupied(mut entry) => {
+					if other_metadata.updated_at > entry.get().updated_at {
+						entry.insert(other_metadata);
+					}
+				}
+				Entry::Vacant(entry) => {
+					entry.insert(other_metadata);
+				}
+			}
+		}
+
+		self.metadata.created_at = self.metadata.created_at.min(other.metadata.created_at);
+		self.metadata.updated_at = Utc::now();
+
+		self.write_metadata().await
+	}
+
 	pub fn has_library(&self, library_id: LibraryId) -> bool {
 		self.metadata.libraries.contains_key(&library_id)
 	}
@@ -195,6 +700,19 @@ impl SpacedriveLocationMetadataFile {
 			.map(|l| l.path.as_path())
 	}
 
+	/// Libraries that registered this location.
+	pub fn library_ids(&self) -> impl Iterator<Item = LibraryId> + '_ {
+		self.metadata.libraries.keys().copied()
+	}
+
+	/// Path and name of the location for each library that registered it.
+	pub fn entries(&self) -> impl Iterator<Item = (LibraryId, &Path, &str)> {
+		self.metadata
+			.libraries
+			.iter()
+			.map(|(library_id, l)| (*library_id, l.path.as_path(), l.name.as_str()))
+	}
+
 	pub fn is_empty(&self) -> bool {
 		self.metadata.libraries.is_empty()
 	}
@@ -203,6 +721,9 @@ impl SpacedriveLocationMetadataFile {
 		&mut self,
 		library_id: LibraryId,
 	) -> Result<(), LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+		self.refresh().await?;
+
 		self.metadata
 			.libraries
 			.remove(&library_id)
@@ -213,34 +734,56 @@ impl SpacedriveLocationMetadataFile {
 		if !self.metadata.libraries.is_empty() {
 			self.write_metadata().await
 		} else {
-			fs::remove_file(&self.path)
-				.await
-				.map_err(|e| LocationMetadataError::Delete(e, self.path.clone()))
+			self.remove_metadata().await
 		}
 	}
 
+	/// Removes the libraries that no longer exist, returning their ids. The metadata file is
+	/// deleted if no library is left.
 	pub async fn clean_stale_libraries(
 		&mut self,
 		existing_libraries_ids: &HashSet<LibraryId>,
-	) -> Result<(), LocationMetadataError> {
-		let previous_libraries_count = self.metadata.libraries.len();
-		self.metadata
+	) -> Result<Vec<LibraryId>, LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+		self.refresh().await?;
+
+		let removed_libraries_ids = self
+			.metadata
 			.libraries
-			.retain(|library_id, _| existing_libraries_ids.contains(library_id));
+			.keys()
+			.filter(|library_id| !existing_libraries_ids.contains(library_id))
+			.copied()
+			.collect::<Vec<_>>();
+
+		if removed_libraries_ids.is_empty() {
+			return Ok(removed_libraries_ids);
+		}
 
-		if self.metadata.libraries.len() != previous_libraries_count {
-			self.metadata.updated_at = Utc::now();
+		for library_id in &removed_libraries_ids {
+			self.metadata.libraries.remove(library_id);
+		}
 
-			if !self.metadata.libraries.is_empty() {
-				self.write_metadata().await
-			} else {
-				fs::remove_file(&self.path)
-					.await
-					.map_err(|e| LocationMetadataError::Delete(e, self.path.clone()))
-			}
+		self.metadata.updated_at = Utc::now();
+
+		if !self.metadata.libraries.is_empty() {
+			self.write_metadata().await?;
 		} else {
-			Ok(())
+			self.remove_metadata().await?;
 		}
+
+		Ok(removed_libraries_ids)
+	}
+
+	/// Same as [`SpacedriveLocationMetadataFile::clean_stale_libraries`], for every metadata file
+	/// found under `root`, like the nested locations of a reconnected drive. Returns the path of
+	/// the location along with each removed library.
+	pub async fn clean_stale_libraries_recursive(
+		root: impl AsRef<Path>,
+		existing_libraries_ids: &HashSet<LibraryId>,
+	) -> Result<Vec<(PathBuf, LibraryId)>, LocationMetadataError> {
+		Self::builder()
+			.clean_stale_libraries_recursive(root, existing_libraries_ids)
+			.await
 	}
 
 	pub fn location_pub_id(&self, library_id: LibraryId) -> Result<Uuid, LocationMetadataError> {
@@ -251,32 +794,536 @@ impl SpacedriveLocationMetadataFile {
 			.map(|m| m.pub_id)
 	}
 
-	async fn write_metadata(&self) -> Result<(), LocationMetadataError> {
-		let mut file_options = OpenOptions::new();
+	pub fn metadata_for(&self, library_id: LibraryId) -> Option<LocationMetadataView> {
+		self.metadata.libraries.get(&library_id).map(Into::into)
+	}
 
-		// we want to write the file if it exists, otherwise create it
-		file_options.create(true).write(true);
+	pub fn fingerprint(&self, library_id: LibraryId) -> Option<LocationFingerprint> {
+		self.metadata
+			.libraries
+			.get(&library_id)
+			.map(|l| LocationFingerprint {
+				pub_id: l.pub_id,
+				path: normalize_path(&l.path),
+			})
+	}
 
-		#[cfg(target_os = "windows")]
-		{
-			use windows::Win32::Storage::FileSystem::FILE_ATTRIBUTE_HIDDEN;
-			file_options.attributes(FILE_ATTRIBUTE_HIDDEN.0);
+	/// Upgrades metadata written with an older layout to the current one, rewriting the file.
+	async fn migrate(&mut self) -> Result<(), LocationMetadataError> {
+		match self.metadata.version {
+			METADATA_VERSION => return Ok(()),
+			version if version > METADATA_VERSION => {
+				return Err(LocationMetadataError::UnsupportedVersion(
+					version,
+					self.path.clone(),
+				));
+			}
+			// Version 1 has the same layout as the unversioned files, only adding the version
+			_ => {}
+		}
+
+		let _lock = lock_metadata_file(&self.path).await;
+
+		debug!(
+			path = %self.path.display(),
+			from = self.metadata.version,
+			to = METADATA_VERSION,
+			"Migrating location metadata file;",
+		);
+
+		self.metadata.version = METADATA_VERSION;
+
+		self.write_metadata().await
+	}
+
+	/// Re-reads the metadata file, replacing the loaded metadata, like after it was changed by
+	/// another instance sharing the drive. Unlike [`SpacedriveLocationMetadataFile::try_load`],
+	/// a missing or corrupted file is an error, leaving the loaded metadata as is.
+	pub async fn reload(&mut self) -> Result<(), LocationMetadataError> {
+		let _lock = lock_metadata_file(&self.path).await;
+
+		let contents = fs::read(&self.path)
+			.await
+			.map_err(|e| LocationMetadataError::Read(e, self.path.clone()))?;
+
+		self.metadata = decode_metadata(&contents, &self.path, self.encryption_key.as_ref())?;
+
+		self.update_cache().await;
+
+		Ok(())
+	}
+
+	/// Emits every time the metadata file changes on disk, so the caller can
+	/// [`reload`](SpacedriveLocationMetadataFile::reload) it. Our own writes are emitted too.
+	///
+	/// The file's directory is watched instead of the file itself, as every write replaces it.
+	pub fn watch(&self) -> impl Stream<Item = Result<(), LocationMetadataError>> + Send + 'static {
+		let path = self.path.clone();
+
+		try_stream! {
+			let (events_tx, events_rx) = chan::unbounded();
+
+			let mut watcher = RecommendedWatcher::new(
+				move |result| {
+					// SAFETY: we are not blocking the thread as this is an unbounded channel, and
+					// it's only closed once the stream is dropped, so there is no one to notify
+					let _ = events_tx.send_blocking(result);
+				},
+				Config::default(),
+			)
+			.map_err(|e| LocationMetadataError::Watch(e, path.clone()))?;
+
+			watcher
+				.watch(path.parent().unwrap_or(&path), RecursiveMode::NonRecursive)
+				.map_err(|e| LocationMetadataError::Watch(e, path.clone()))?;
+
+			while let Ok(result) = events_rx.recv().await {
+				let event = result.map_err(|e| LocationMetadataError::Watch(e, path.clone()))?;
+
+				if !matches!(event.kind, EventKind::Access(_))
+					&& event.paths.iter().any(|event_path| event_path == &path)
+				{
+					yield ();
+				}
+			}
+		}
+	}
+
+	async fn refresh(&mut self) -> Result<(), LocationMetadataError> {
+		let path = self.path.clone();
+		self.refresh_from(&path).await
+	}
+
+	/// Re-reads the metadata from `path`, to apply our changes on top of the ones written by
+	/// others since we loaded it. Keeps the current metadata only if there is no file there, as
+	/// we're about to create it.
+	///
+	/// Fails if the file can't be decoded, like one encrypted with a key we don't have or written
+	/// by a newer version, as overwriting it with our stale metadata would drop the changes of
+	/// others, downgrade its format or write it back in plain text.
+	async fn refresh_from(&mut self, path: &Path) -> Result<(), LocationMetadataError> {
+		match fs::read(path).await {
+			Ok(contents) => {
+				self.metadata = decode_metadata(&contents, path, self.encryption_key.as_ref())?;
+			}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(LocationMetadataError::Read(e, path.to_path_buf())),
 		}
 
-		let metadata_contents = serde_json::to_vec(&self.metadata)
+		Ok(())
+	}
+
+	/// Writes the metadata to a temporary sibling file, then renames it over the actual one, so
+	/// the metadata file is always either the old or the new complete version, even if we die
+	/// mid-write.
+	async fn write_metadata(&self) -> Result<(), LocationMetadataError> {
+		let mut metadata_contents = encode_metadata(&self.metadata)
 			.map_err(|e| LocationMetadataError::Serialize(e, self.path.clone()))?;
 
-		file_options
-			.open(&self.path)
+		if let Some(encryption_key) = &self.encryption_key {
+			metadata_contents = encrypt_contents(&metadata_contents, encryption_key, &self.path)?;
+		}
+
+		let backup_path = with_suffix(&self.path, BACKUP_FILE_SUFFIX);
+
+		// Keeping the previous version around, to fall back to if the new one gets corrupted
+		match fs::copy(&self.path, &backup_path).await {
+			Ok(_) => {}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(LocationMetadataError::Write(e, backup_path)),
+		}
+
+		let temporary_path = with_suffix(&self.path, TEMPORARY_FILE_SUFFIX);
+
+		// The live file is only replaced once the new contents are fully written
+		if let Err(e) = self
+			.write_temporary_file(&temporary_path, &metadata_contents)
 			.await
-			.map_err(|e| LocationMetadataError::Write(e, self.path.clone()))?
-			.write_all(&metadata_contents)
+		{
+			if let Err(e) = fs::remove_file(&temporary_path).await {
+				warn!(
+					temporary_path = %temporary_path.display(),
+					?e,
+					"Failed to remove temporary metadata file after a failed write;",
+				);
+			}
+
+			return Err(LocationMetadataError::Write(e, temporary_path));
+		}
+
+		fs::rename(&temporary_path, &self.path)
 			.await
-			.map_err(|e| LocationMetadataError::Write(e, self.path.clone()))
+			.map_err(|e| LocationMetadataError::Write(e, self.path.clone()))?;
+
+		// The rename itself is only durable once the directory is synced
+		#[cfg(unix)]
+		if self.durable_writes {
+			if let Some(parent) = self.path.parent() {
+				fs::File::open(parent)
+					.await
+					.map_err(|e| LocationMetadataError::Write(e, parent.to_path_buf()))?
+					.sync_all()
+					.await
+					.map_err(|e| LocationMetadataError::Write(e, parent.to_path_buf()))?;
+			}
+		}
+
+		self.update_cache().await;
+
+		Ok(())
 	}
-}
 
-#[derive(Error, Debug)]
+	async fn update_cache(&self) {
+		let canonical_path = canonical_file_path(&self.path).await;
+
+		// Other caches may hold what was there before, loaded by other instances of this file
+		invalidate_cached(&canonical_path);
+
+		if let Some(cache) = &self.cache {
+			cache.insert(canonical_path, Arc::new(self.metadata.clone()));
+		}
+	}
+
+	async fn write_temporary_file(
+		&self,
+		temporary_path: &Path,
+		metadata_contents: &[u8],
+	) -> Result<(), io::Error> {
+		let mut file_options = OpenOptions::new();
+
+		// a leftover temporary file from an interrupted write is overwritten
+		file_options.create(true).write(true).truncate(true);
+
+		// the hidden attribute is kept by the rename
+		#[cfg(target_os = "windows")]
+		{
+			use windows::Win32::Storage::FileSystem::FILE_ATTRIBUTE_HIDDEN;
+			file_options.attributes(FILE_ATTRIBUTE_HIDDEN.0);
+		}
+
+		let mut file = file_options.open(temporary_path).await?;
+
+		file.write_all(metadata_contents).await?;
+		file.flush().await?;
+
+		if self.durable_writes {
+			file.sync_all().await?;
+		}
+
+		// Windows doesn't allow renaming a file with open handles, so it's closed on return
+
+		Ok(())
+	}
+
+	/// Deletes the metadata file along with its backup, which would otherwise bring back the
+	/// removed libraries if restored.
+	async fn remove_metadata(&self) -> Result<(), LocationMetadataError> {
+		fs::remove_file(&self.path)
+			.await
+			.map_err(|e| LocationMetadataError::Delete(e, self.path.clone()))?;
+
+		invalidate_cached(&canonical_file_path(&self.path).await);
+
+		let backup_path = with_suffix(&self.path, BACKUP_FILE_SUFFIX);
+
+		match fs::remove_file(&backup_path).await {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => {
+				Err(LocationMetadataError::Delete(e, backup_path))
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Whether `library_id` already registered the location at `candidate_path`, as found in its
+/// metadata file, even if it was registered through another path, like a symlink or another
+/// mount point of the same drive.
+pub async fn is_location_owned_by(
+	library_id: LibraryId,
+	candidate_path: impl AsRef<Path>,
+) -> Result<bool, LocationMetadataError> {
+	Ok(SpacedriveLocationMetadataFile::try_load(candidate_path)
+		.await?
+		.is_some_and(|metadata_file| metadata_file.has_library(library_id)))
+}
+
+type CachedMetadata = Cache<PathBuf, Arc<SpacedriveLocationMetadata>>;
+
+/// Keeps recently loaded metadata files in memory, keyed by their canonical path, so locations
+/// touched repeatedly don't read and parse their metadata file every time.
+///
+/// Writes through any metadata file of this process drop the stale entries of every cache, but
+/// changes made by other processes are only seen once their entry expires or is
+/// [invalidated](MetadataCache::invalidate).
+#[derive(Clone)]
+pub struct MetadataCache {
+	cache: Arc<CachedMetadata>,
+	builder: LocationMetadataFileBuilder,
+}
+
+impl MetadataCache {
+	/// Keeps up to `capacity` files, for `ttl` each.
+	#[must_use]
+	pub fn new(capacity: u64, ttl: Duration) -> Self {
+		Self::with_builder(SpacedriveLocationMetadataFile::builder(), capacity, ttl)
+	}
+
+	/// Same as [`MetadataCache::new`], loading the files with `builder`.
+	#[must_use]
+	pub fn with_builder(
+		builder: LocationMetadataFileBuilder,
+		capacity: u64,
+		ttl: Duration,
+	) -> Self {
+		let cache = Arc::new(
+			Cache::builder()
+				.max_capacity(capacity)
+				.time_to_live(ttl)
+				.build(),
+		);
+
+		let mut caches = METADATA_CACHES
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+
+		// Dropping the caches that nobody is using anymore
+		caches.retain(|cache| cache.strong_count() > 0);
+		caches.push(Arc::downgrade(&cache));
+
+		Self { cache, builder }
+	}
+
+	/// Same as [`SpacedriveLocationMetadataFile::try_load`], from the cache if possible.
+	pub async fn try_load(
+		&self,
+		location_path: impl AsRef<Path>,
+	) -> Result<Option<SpacedriveLocationMetadataFile>, LocationMetadataError> {
+		let path = location_path.as_ref().join(&self.builder.file_name);
+		let canonical_path = canonical_file_path(&path).await;
+
+		if let Some(metadata) = self.cache.get(&canonical_path) {
+			return Ok(Some(SpacedriveLocationMetadataFile {
+				path,
+				file_name: self.builder.file_name.clone(),
+				metadata: metadata.as_ref().clone(),
+				encryption_key: self.builder.encryption_key.clone(),
+				durable_writes: self.builder.durable_writes,
+				cache: Some(CachedMetadata::clone(&self.cache)),
+			}));
+		}
+
+		let Some(mut metadata_file) = self.builder.clone().try_load(location_path).await? else {
+			return Ok(None);
+		};
+
+		self.cache
+			.insert(canonical_path, Arc::new(metadata_file.metadata.clone()));
+		metadata_file.cache = Some(CachedMetadata::clone(&self.cache));
+
+		Ok(Some(metadata_file))
+	}
+
+	/// Drops the cached metadata of the location at `location_path`, if any.
+	pub async fn invalidate(&self, location_path: impl AsRef<Path>) {
+		self.cache.invalidate(
+			&canonical_file_path(&location_path.as_ref().join(&self.builder.file_name)).await,
+		);
+	}
+}
+
+/// Canonical form of the path of a metadata file, which may not exist yet, so only its directory
+/// is canonicalized.
+async fn canonical_file_path(path: &Path) -> PathBuf {
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(file_name)) => fs::canonicalize(parent)
+			.await
+			.map_or_else(|_| path.to_path_buf(), |parent| parent.join(file_name)),
+		_ => path.to_path_buf(),
+	}
+}
+
+/// Drops the metadata file at `canonical_path` from every [`MetadataCache`] of this process.
+fn invalidate_cached(canonical_path: &Path) {
+	let caches = METADATA_CACHES
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.iter()
+		.filter_map(Weak::upgrade)
+		.collect::<Vec<_>>();
+
+	for cache in caches {
+		cache.invalidate(canonical_path);
+	}
+}
+
+/// Locks the metadata file at `path` for this process, until the returned guard is dropped.
+async fn lock_metadata_file(path: &Path) -> OwnedMutexGuard<()> {
+	let canonical_path = canonical_file_path(path).await;
+
+	let lock = {
+		let mut locks = METADATA_FILE_LOCKS.lock().await;
+
+		// Dropping the locks that nobody is holding nor waiting for
+		locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
+		Arc::clone(locks.entry(canonical_path).or_default())
+	};
+
+	lock.lock_owned().await
+}
+
+/// Reads the backup of the metadata file at `metadata_file_name`, if there is a valid one.
+async fn read_backup(
+	metadata_file_name: &Path,
+	encryption_key: Option<&SecretKey>,
+) -> Option<SpacedriveLocationMetadata> {
+	let backup_path = with_suffix(metadata_file_name, BACKUP_FILE_SUFFIX);
+
+	let data = match fs::read(&backup_path).await {
+		Ok(data) => data,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+		Err(e) => {
+			error!(backup_path = %backup_path.display(), ?e, "Failed to read metadata backup;");
+			return None;
+		}
+	};
+
+	match decode_metadata(&data, &backup_path, encryption_key) {
+		Ok(metadata) => {
+			warn!(
+				backup_path = %backup_path.display(),
+				"Restoring location metadata from its backup;",
+			);
+
+			Some(metadata)
+		}
+		Err(e) => {
+			error!(
+				backup_path = %backup_path.display(),
+				?e,
+				"Failed to deserialize corrupted metadata backup;",
+			);
+
+			None
+		}
+	}
+}
+
+fn checksum(data: &serde_json::Value) -> Result<String, serde_json::Error> {
+	// Values keep their maps' keys in a stable order, so the same data always has the same checksum
+	Ok(blake3::hash(&serde_json::to_vec(data)?)
+		.to_hex()
+		.to_string())
+}
+
+fn encode_metadata(metadata: &SpacedriveLocationMetadata) -> Result<Vec<u8>, serde_json::Error> {
+	let data = serde_json::to_value(metadata)?;
+
+	serde_json::to_vec(&ChecksummedMetadata {
+		checksum: checksum(&data)?,
+		data,
+	})
+}
+
+fn derive_encryption_key(key_material: &Protected<Vec<u8>>) -> SecretKey {
+	SecretKey::from(Protected::new(blake3::derive_key(
+		ENCRYPTION_KEY_CONTEXT,
+		key_material.expose(),
+	)))
+}
+
+fn encrypt_contents(
+	contents: &[u8],
+	encryption_key: &SecretKey,
+	path: &Path,
+) -> Result<Vec<u8>, LocationMetadataError> {
+	let crypto_error = |e| LocationMetadataError::Crypto(e, path.to_path_buf());
+
+	let EncryptedBlock { nonce, cipher_text } = encryption_key
+		.encrypt(contents, &mut CryptoRng::new().map_err(crypto_error)?)
+		.map_err(crypto_error)?;
+
+	serde_json::to_vec(&EncryptedMetadata {
+		encrypted: hex::encode([nonce.as_slice(), &cipher_text].concat()),
+	})
+	.map_err(|e| LocationMetadataError::Serialize(e, path.to_path_buf()))
+}
+
+/// Deserializes the contents of the metadata file at `path`, decrypting it with `encryption_key`
+/// if it's encrypted, and verifying its checksum if it has one.
+fn decode_metadata(
+	contents: &[u8],
+	path: &Path,
+	encryption_key: Option<&SecretKey>,
+) -> Result<SpacedriveLocationMetadata, LocationMetadataError> {
+	let deserialize_error = |e| LocationMetadataError::Deserialize(e, path.to_path_buf());
+
+	match serde_json::from_slice(contents).map_err(deserialize_error)? {
+		MetadataFileContents::Checksummed(ChecksummedMetadata {
+			checksum: expected,
+			data,
+		}) => {
+			let actual = checksum(&data)
+				.map_err(|e| LocationMetadataError::Serialize(e, path.to_path_buf()))?;
+
+			if actual != expected {
+				return Err(LocationMetadataError::ChecksumMismatch(path.to_path_buf()));
+			}
+
+			serde_json::from_value(data).map_err(deserialize_error)
+		}
+		MetadataFileContents::Encrypted(EncryptedMetadata { encrypted }) => {
+			let encryption_key = encryption_key
+				.ok_or_else(|| LocationMetadataError::EncryptionKeyRequired(path.to_path_buf()))?;
+
+			let decrypt_error =
+				|| LocationMetadataError::Crypto(CryptoError::Decrypt, path.to_path_buf());
+
+			let encrypted = hex::decode(encrypted).map_err(|_| decrypt_error())?;
+
+			if encrypted.len() < size_of::<OneShotNonce>() {
+				return Err(decrypt_error());
+			}
+
+			let decrypted = encryption_key
+				.decrypt(EncryptedBlockRef::from(encrypted.as_slice()))
+				.map_err(|e| LocationMetadataError::Crypto(e, path.to_path_buf()))?;
+
+			decode_metadata(&decrypted, path, None)
+		}
+		MetadataFileContents::Unchecked(metadata) => Ok(metadata),
+	}
+}
+
+/// Normalizes `path` for comparisons, dropping trailing and repeated separators, and folding its
+/// case on Windows, where paths are case insensitive.
+fn normalize_path(path: &Path) -> PathBuf {
+	let path = path.components().collect::<PathBuf>();
+
+	#[cfg(target_os = "windows")]
+	{
+		PathBuf::from(path.to_string_lossy().to_lowercase())
+	}
+
+	#[cfg(not(target_os = "windows"))]
+	{
+		path
+	}
+}
+
+fn is_same_path(a: &Path, b: &Path) -> bool {
+	normalize_path(a) == normalize_path(b)
+}
+
+/// Appends `suffix` to the file name of `path`, like `.spacedrive` to `.spacedrive.tmp`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+	let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+	file_name.push(suffix);
+
+	path.with_file_name(file_name)
+}
+
+#[derive(Error, Debug)]
 pub enum LocationMetadataError {
 	#[error("Library not found: {0}")]
 	LibraryNotFound(LibraryId),
@@ -290,6 +1337,530 @@ pub enum LocationMetadataError {
 	Write(io::Error, PathBuf),
 	#[error("Failed to deserialize metadata file for location (at path: {1:?}); (error: {0:?})")]
 	Deserialize(serde_json::Error, PathBuf),
+	#[error("Location metadata file doesn't match its checksum (path: {0:?})")]
+	ChecksumMismatch(PathBuf),
+	#[error("Location metadata file is encrypted, but no key was given (path: {0:?})")]
+	EncryptionKeyRequired(PathBuf),
+	#[error("Failed to encrypt or decrypt location metadata file (path: {1:?}); (error: {0:?})")]
+	Crypto(CryptoError, PathBuf),
+	#[error("Failed to watch location metadata file (path: {1:?}); (error: {0:?})")]
+	Watch(notify::Error, PathBuf),
+	#[error("Failed to move aside corrupted location metadata file (path: {1:?}); (error: {0:?})")]
+	MoveCorrupted(io::Error, PathBuf),
+	#[error(
+		"Location metadata file has version {0}, newer than the supported {METADATA_VERSION} \
+		(path: {1:?})"
+	)]
+	UnsupportedVersion(u32, PathBuf),
 	#[error("Failed to relink, as the new location path is the same as the old path: {0}")]
 	RelinkSamePath(PathBuf),
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::tempdir;
+
+	#[test]
+	#[should_panic(expected = "must start with `.spacedrive.`")]
+	fn custom_file_name_keeps_reserved_prefix() {
+		assert!(is_metadata_file_name(".spacedrive.my-app"));
+		assert!(is_metadata_file_name(".spacedrive.my-app.bak"));
+		assert!(!is_metadata_file_name(".spacedrive-notes"));
+
+		let _ = SpacedriveLocationMetadataFile::builder().file_name("my-app.json");
+	}
+
+	#[tokio::test]
+	async fn undecodable_file_aborts_changes() {
+		let location_dir = tempdir().unwrap();
+		let metadata_path = location_dir.path().join(SPACEDRIVE_LOCATION_METADATA_FILE);
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			Uuid::new_v4(),
+			Uuid::new_v4(),
+			location_dir.path(),
+			"location".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata_file = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		// Like a file written by a newer version, or encrypted by another instance
+		std::fs::write(&metadata_path, b"not our metadata").unwrap();
+
+		assert!(metadata_file
+			.add_library(
+				Uuid::new_v4(),
+				Uuid::new_v4(),
+				location_dir.path(),
+				"location".to_string(),
+			)
+			.await
+			.is_err());
+		assert_eq!(std::fs::read(&metadata_path).unwrap(), b"not our metadata");
+	}
+
+	#[tokio::test]
+	async fn update_all_renames_every_library() {
+		let location_dir = tempdir().unwrap();
+		let (first_library_id, second_library_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			first_library_id,
+			Uuid::new_v4(),
+			location_dir.path(),
+			"first".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+		metadata
+			.add_library(
+				second_library_id,
+				Uuid::new_v4(),
+				location_dir.path(),
+				"second".to_string(),
+			)
+			.await
+			.unwrap();
+
+		let previously_updated_at = metadata.metadata.updated_at;
+
+		metadata.update_all("renamed".to_string()).await.unwrap();
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		for library_id in [first_library_id, second_library_id] {
+			assert_eq!(metadata.metadata_for(library_id).unwrap().name, "renamed");
+		}
+		assert!(metadata.metadata.updated_at > previously_updated_at);
+	}
+
+	#[tokio::test]
+	async fn failed_write_keeps_previous_metadata() {
+		let location_dir = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			Uuid::new_v4(),
+			location_dir.path(),
+			"old name".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		// A directory in place of the temporary file makes the write fail
+		let temporary_path = with_suffix(
+			&location_dir.path().join(SPACEDRIVE_LOCATION_METADATA_FILE),
+			TEMPORARY_FILE_SUFFIX,
+		);
+		std::fs::create_dir(&temporary_path).unwrap();
+
+		assert!(matches!(
+			metadata.update(library_id, "new name".to_string()).await,
+			Err(LocationMetadataError::Write(..))
+		));
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		assert_eq!(metadata.metadata_for(library_id).unwrap().name, "old name");
+	}
+
+	#[test]
+	fn same_path_ignores_trailing_separators() {
+		assert!(is_same_path(Path::new("/foo/bar"), Path::new("/foo/bar/")));
+		assert!(is_same_path(Path::new("/foo//bar"), Path::new("/foo/bar")));
+		assert!(!is_same_path(Path::new("/foo/bar"), Path::new("/foo/baz")));
+	}
+
+	#[test]
+	#[cfg(target_os = "windows")]
+	fn same_path_ignores_case_on_windows() {
+		assert!(is_same_path(Path::new(r"C:\Foo"), Path::new(r"c:\foo\")));
+		assert!(!is_same_path(Path::new(r"C:\Foo"), Path::new(r"C:\Bar")));
+	}
+
+	#[tokio::test]
+	async fn relink_to_same_path_with_trailing_separator() {
+		let location_dir = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			Uuid::new_v4(),
+			location_dir.path(),
+			"location".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		let mut path_with_separator = location_dir.path().as_os_str().to_owned();
+		path_with_separator.push(std::path::MAIN_SEPARATOR_STR);
+
+		assert!(!metadata.needs_relink(library_id, &path_with_separator));
+		assert!(matches!(
+			metadata.relink(library_id, &path_with_separator).await,
+			Err(LocationMetadataError::RelinkSamePath(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn clean_stale_libraries_in_nested_locations() {
+		let root_dir = tempdir().unwrap();
+		let nested_dir = root_dir.path().join("a").join("b");
+		std::fs::create_dir_all(&nested_dir).unwrap();
+
+		let existing_library_id = Uuid::new_v4();
+		let stale_library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			stale_library_id,
+			Uuid::new_v4(),
+			root_dir.path(),
+			"root".to_string(),
+		)
+		.await
+		.unwrap();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			existing_library_id,
+			Uuid::new_v4(),
+			&nested_dir,
+			"nested".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut nested = SpacedriveLocationMetadataFile::try_load(&nested_dir)
+			.await
+			.unwrap()
+			.unwrap();
+		nested
+			.add_library(
+				stale_library_id,
+				Uuid::new_v4(),
+				&nested_dir,
+				"nested".to_string(),
+			)
+			.await
+			.unwrap();
+
+		let mut removed = SpacedriveLocationMetadataFile::clean_stale_libraries_recursive(
+			root_dir.path(),
+			&HashSet::from([existing_library_id]),
+		)
+		.await
+		.unwrap();
+		removed.sort();
+
+		let mut expected = vec![
+			(root_dir.path().to_path_buf(), stale_library_id),
+			(nested_dir.clone(), stale_library_id),
+		];
+		expected.sort();
+
+		assert_eq!(removed, expected);
+
+		assert!(SpacedriveLocationMetadataFile::try_load(root_dir.path())
+			.await
+			.unwrap()
+			.is_none());
+
+		let nested = SpacedriveLocationMetadataFile::try_load(&nested_dir)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			nested.library_ids().collect::<Vec<_>>(),
+			[existing_library_id]
+		);
+	}
+
+	#[tokio::test]
+	async fn clean_stale_libraries_skips_unreadable_nested_files() {
+		let root_dir = tempdir().unwrap();
+		let bad_dir = root_dir.path().join("bad");
+		let good_dir = root_dir.path().join("good");
+		std::fs::create_dir_all(&bad_dir).unwrap();
+		std::fs::create_dir_all(&good_dir).unwrap();
+
+		let stale_library_id = Uuid::new_v4();
+
+		// Can't be loaded without its key
+		SpacedriveLocationMetadataFile::create_and_save_encrypted(
+			stale_library_id,
+			Uuid::new_v4(),
+			&bad_dir,
+			"bad".to_string(),
+			&Protected::new(vec![42; 32]),
+		)
+		.await
+		.unwrap();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			stale_library_id,
+			Uuid::new_v4(),
+			&good_dir,
+			"good".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let removed = SpacedriveLocationMetadataFile::clean_stale_libraries_recursive(
+			root_dir.path(),
+			&HashSet::new(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(removed, [(good_dir.clone(), stale_library_id)]);
+		assert!(SpacedriveLocationMetadataFile::try_load(&good_dir)
+			.await
+			.unwrap()
+			.is_none());
+		assert!(bad_dir.join(SPACEDRIVE_LOCATION_METADATA_FILE).exists());
+	}
+
+	#[tokio::test]
+	#[cfg(unix)]
+	async fn location_owned_through_symlink() {
+		let location_dir = tempdir().unwrap();
+		let link_dir = tempdir().unwrap();
+		let link_path = link_dir.path().join("link");
+		std::os::unix::fs::symlink(location_dir.path(), &link_path).unwrap();
+
+		let library_id = Uuid::new_v4();
+		let location_pub_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			location_pub_id,
+			location_dir.path(),
+			"location".to_string(),
+		)
+		.await
+		.unwrap();
+
+		assert!(is_location_owned_by(library_id, &link_path).await.unwrap());
+		assert!(!is_location_owned_by(Uuid::new_v4(), &link_path)
+			.await
+			.unwrap());
+		assert!(!is_location_owned_by(library_id, link_dir.path())
+			.await
+			.unwrap());
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(&link_path)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(
+			metadata.fingerprint(library_id),
+			Some(LocationFingerprint {
+				pub_id: location_pub_id,
+				path: normalize_path(location_dir.path()),
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn update_returns_previous_name() {
+		let location_dir = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			Uuid::new_v4(),
+			location_dir.path(),
+			"old name".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		assert_eq!(
+			metadata
+				.update(library_id, "new name".to_string())
+				.await
+				.unwrap(),
+			"old name"
+		);
+		assert_eq!(metadata.metadata_for(library_id).unwrap().name, "new name");
+
+		assert!(matches!(
+			metadata
+				.update(Uuid::new_v4(), "other name".to_string())
+				.await,
+			Err(LocationMetadataError::LibraryNotFound(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn reconcile_self_path_only_relinks_moved_locations() {
+		let root_dir = tempdir().unwrap();
+		let old_path = root_dir.path().join("old mount");
+		let new_path = root_dir.path().join("new mount");
+		std::fs::create_dir(&old_path).unwrap();
+
+		let library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			Uuid::new_v4(),
+			&old_path,
+			"location".to_string(),
+		)
+		.await
+		.unwrap();
+
+		std::fs::rename(&old_path, &new_path).unwrap();
+
+		let mut metadata = SpacedriveLocationMetadataFile::try_load(&new_path)
+			.await
+			.unwrap()
+			.unwrap();
+
+		assert!(metadata
+			.reconcile_self_path(library_id, &new_path)
+			.await
+			.unwrap());
+		assert!(!metadata
+			.reconcile_self_path(library_id, &new_path)
+			.await
+			.unwrap());
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(&new_path)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(metadata.location_path(library_id), Some(new_path.as_path()));
+	}
+
+	#[tokio::test]
+	async fn cache_is_updated_by_every_file() {
+		async fn cached_name(cache: &MetadataCache, path: &Path, library_id: LibraryId) -> String {
+			let metadata = cache.try_load(path).await.unwrap().unwrap();
+			metadata.metadata_for(library_id).unwrap().name
+		}
+
+		let location_dir = tempdir().unwrap();
+		let library_id = Uuid::new_v4();
+
+		SpacedriveLocationMetadataFile::create_and_save(
+			library_id,
+			Uuid::new_v4(),
+			location_dir.path(),
+			"one".to_string(),
+		)
+		.await
+		.unwrap();
+
+		let cache = MetadataCache::new(10, Duration::from_secs(60));
+
+		let mut cached = cache.try_load(location_dir.path()).await.unwrap().unwrap();
+
+		// Changes through files not loaded from the cache are seen too
+		SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap()
+			.update(library_id, "two".to_string())
+			.await
+			.unwrap();
+		assert_eq!(
+			cached_name(&cache, location_dir.path(), library_id).await,
+			"two"
+		);
+
+		// Just like changes through the cached ones
+		assert_eq!(
+			cached
+				.update(library_id, "three".to_string())
+				.await
+				.unwrap(),
+			"two"
+		);
+		assert_eq!(
+			cached_name(&cache, location_dir.path(), library_id).await,
+			"three"
+		);
+
+		SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap()
+			.update(library_id, "four".to_string())
+			.await
+			.unwrap();
+		cache.invalidate(location_dir.path()).await;
+		assert_eq!(
+			cached_name(&cache, location_dir.path(), library_id).await,
+			"four"
+		);
+
+		cached.remove_library(library_id).await.unwrap();
+		assert!(cache.try_load(location_dir.path()).await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn create_keeps_other_libraries() {
+		let location_dir = tempdir().unwrap();
+		let (first_library_id, second_library_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+		for (library_id, location_name) in
+			[(first_library_id, "first"), (second_library_id, "second")]
+		{
+			SpacedriveLocationMetadataFile::create_and_save(
+				library_id,
+				Uuid::new_v4(),
+				location_dir.path(),
+				location_name.to_string(),
+			)
+			.await
+			.unwrap();
+		}
+
+		let metadata = SpacedriveLocationMetadataFile::try_load(location_dir.path())
+			.await
+			.unwrap()
+			.unwrap();
+
+		assert_eq!(
+			metadata.metadata_for(first_library_id).unwrap().name,
+			"first"
+		);
+		assert_eq!(
+			metadata.metadata_for(second_library_id).unwrap().name,
+			"second"
+		);
+	}
+}
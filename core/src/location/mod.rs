@@ -343,9 +343,11 @@ impl LocationUpdateArgs {
 					if let Some(mut metadata) =
 						SpacedriveLocationMetadataFile::try_load(path).await?
 					{
-						metadata
+						let previous_name = metadata
 							.update(library.id, maybe_missing(name, "location.name")?)
 							.await?;
+
+						debug!(%previous_name, "Renamed location in its metadata file;");
 					}
 				}
 			}
@@ -2,11 +2,21 @@
 #![allow(unused)]
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use specta::Type;
+use specta::{DataType, Generics, Type, TypeMap};
 
-// This exports an incorrect Typescript type. https://github.com/oscartbeaumont/specta/issues/157
-#[derive(Debug, Clone, Type)]
-#[specta(untagged)]
+/// A value that can be missing (`Undefined`), explicitly `null` (`Null`) or present (`Value`).
+///
+/// Serde can only tell a missing field apart from a `null` one if the field falls back to
+/// [`Default`], so struct fields of this type must be marked with `#[serde(default)]`:
+/// a missing field then becomes `Undefined` while an explicit `null` becomes `Null`.
+///
+/// `Undefined` is serialized as `null`, so round-tripping it through a struct also requires
+/// `#[serde(skip_serializing_if = "MaybeUndefined::is_undefined")]`, else it comes back as
+/// `Null`. Values of a `T` serialized as `null`, like `()` or `Option<T>`, come back as `Null`.
+///
+/// The exported Typescript type is `T | null`, the field also needs `#[specta(optional)]` to be
+/// allowed to be `undefined`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MaybeUndefined<T> {
 	Undefined,
 	Null,
@@ -24,12 +34,172 @@ impl<T> MaybeUndefined<T> {
 		!matches!(self, Self::Undefined)
 	}
 
+	// Returns the `Value(T)`, panicking with `msg` on `Undefined` and `Null`. Meant for tests,
+	// production code should handle every variant.
+	#[track_caller]
+	pub fn expect(self, msg: &str) -> T {
+		match self {
+			Self::Value(v) => v,
+			_ => panic!("{msg}"),
+		}
+	}
+
+	// Returns the `Value(T)`, panicking on `Undefined` and `Null`. Meant for tests, like `expect`.
+	#[track_caller]
+	pub fn unwrap(self) -> T {
+		match self {
+			Self::Value(v) => v,
+			Self::Undefined => panic!("called `MaybeUndefined::unwrap()` on an `Undefined` value"),
+			Self::Null => panic!("called `MaybeUndefined::unwrap()` on a `Null` value"),
+		}
+	}
+
 	pub fn unwrap_or(self, t: T) -> T {
 		match self {
 			Self::Value(v) => v,
 			_ => t,
 		}
 	}
+
+	pub fn unwrap_or_else(self, f: impl FnOnce() -> T) -> T {
+		match self {
+			Self::Value(v) => v,
+			_ => f(),
+		}
+	}
+
+	pub fn unwrap_or_default(self) -> T
+	where
+		T: Default,
+	{
+		self.unwrap_or_else(T::default)
+	}
+
+	// `Undefined` will return `Err(err)`, `Null` will return `Ok(None)` and `Value(T)` will return
+	// `Ok(Some(T))`.
+	pub fn ok_or<E>(self, err: E) -> Result<Option<T>, E> {
+		self.ok_or_else(|| err)
+	}
+
+	// Like `ok_or`, but only builds the error if the value is `Undefined`.
+	pub fn ok_or_else<E>(self, err: impl FnOnce() -> E) -> Result<Option<T>, E> {
+		match self {
+			Self::Undefined => Err(err()),
+			Self::Null => Ok(None),
+			Self::Value(v) => Ok(Some(v)),
+		}
+	}
+
+	// Demotes a `Value(T)` failing `predicate` to `Undefined`. `Undefined` and `Null` are
+	// returned as they are.
+	pub fn filter(self, predicate: impl FnOnce(&T) -> bool) -> Self {
+		match self {
+			Self::Value(v) if !predicate(&v) => Self::Undefined,
+			other => other,
+		}
+	}
+
+	// Merges two fields that must be updated together: `Value((T, U))` if both are `Value`,
+	// else `Undefined` if any of them is `Undefined`, leaving both untouched, else `Null`, as at
+	// least one of them is `Null` and the other one is `Null` or `Value`.
+	pub fn zip<U>(self, other: MaybeUndefined<U>) -> MaybeUndefined<(T, U)> {
+		match (self, other) {
+			(Self::Value(t), MaybeUndefined::Value(u)) => MaybeUndefined::Value((t, u)),
+			(Self::Undefined, _) | (_, MaybeUndefined::Undefined) => MaybeUndefined::Undefined,
+			_ => MaybeUndefined::Null,
+		}
+	}
+
+	// Takes the value out, leaving `Undefined` in its place.
+	pub fn take(&mut self) -> Self {
+		std::mem::take(self)
+	}
+
+	// `Undefined` will return `None`, `Null` will return `Some(None)` and `Value(T)` will return
+	// `Some(Some(T))`.
+	pub fn into_option_option(self) -> Option<Option<T>> {
+		self.into()
+	}
+
+	// Builds the update of a nullable field, like a Prisma `SetParam`, skipping it with `None` if
+	// `Undefined`, else setting it to `f(None)` if `Null` or `f(Some(T))` if `Value(T)`.
+	// Consumes the value despite its name, which reads as the field "to update".
+	#[allow(clippy::wrong_self_convention)]
+	pub fn to_update<P>(self, f: impl FnOnce(Option<T>) -> P) -> Option<P> {
+		self.into_option_option().map(f)
+	}
+
+	// Iterates over the value, `Undefined` and `Null` yield nothing.
+	pub fn iter(&self) -> std::option::IntoIter<&T> {
+		self.as_ref().into_iter()
+	}
+
+	// Converts from `&MaybeUndefined<T>` to `MaybeUndefined<&T>`.
+	pub fn as_ref(&self) -> MaybeUndefined<&T> {
+		match self {
+			Self::Undefined => MaybeUndefined::Undefined,
+			Self::Null => MaybeUndefined::Null,
+			Self::Value(v) => MaybeUndefined::Value(v),
+		}
+	}
+
+	// Converts from `&MaybeUndefined<T>` to `MaybeUndefined<&T::Target>`, like a
+	// `MaybeUndefined<&str>` from a `MaybeUndefined<String>`.
+	pub fn as_deref(&self) -> MaybeUndefined<&T::Target>
+	where
+		T: std::ops::Deref,
+	{
+		match self {
+			Self::Undefined => MaybeUndefined::Undefined,
+			Self::Null => MaybeUndefined::Null,
+			Self::Value(v) => MaybeUndefined::Value(&**v),
+		}
+	}
+
+	// Converts from `&mut MaybeUndefined<T>` to `MaybeUndefined<&mut T>`.
+	pub fn as_mut(&mut self) -> MaybeUndefined<&mut T> {
+		match self {
+			Self::Undefined => MaybeUndefined::Undefined,
+			Self::Null => MaybeUndefined::Null,
+			Self::Value(v) => MaybeUndefined::Value(v),
+		}
+	}
+}
+
+// Can't be derived, `#[specta(untagged)]` exports an incorrect Typescript type.
+// https://github.com/oscartbeaumont/specta/issues/157
+impl<T: Type> Type for MaybeUndefined<T> {
+	fn inline(type_map: &mut TypeMap, generics: Generics) -> DataType {
+		DataType::Nullable(Box::new(T::inline(type_map, generics)))
+	}
+}
+
+impl<T> Default for MaybeUndefined<T> {
+	fn default() -> Self {
+		Self::Undefined
+	}
+}
+
+impl<T> IntoIterator for MaybeUndefined<T> {
+	type Item = T;
+	type IntoIter = std::option::IntoIter<T>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		match self {
+			Self::Value(v) => Some(v),
+			_ => None,
+		}
+		.into_iter()
+	}
+}
+
+impl<'a, T> IntoIterator for &'a MaybeUndefined<T> {
+	type Item = &'a T;
+	type IntoIter = std::option::IntoIter<&'a T>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
 }
 
 impl<T> From<MaybeUndefined<T>> for Option<Option<T>> {
@@ -42,6 +212,28 @@ impl<T> From<MaybeUndefined<T>> for Option<Option<T>> {
 	}
 }
 
+impl<T> From<Option<Option<T>>> for MaybeUndefined<T> {
+	fn from(v: Option<Option<T>>) -> MaybeUndefined<T> {
+		match v {
+			None => MaybeUndefined::Undefined,
+			Some(None) => MaybeUndefined::Null,
+			Some(Some(v)) => MaybeUndefined::Value(v),
+		}
+	}
+}
+
+impl<T> MaybeUndefined<MaybeUndefined<T>> {
+	// Removes one level of nesting: an outer `Undefined` or `Null` wins, else the inner value is
+	// returned as is.
+	pub fn flatten(self) -> MaybeUndefined<T> {
+		match self {
+			Self::Undefined => MaybeUndefined::Undefined,
+			Self::Null => MaybeUndefined::Null,
+			Self::Value(inner) => inner,
+		}
+	}
+}
+
 impl<T, E> MaybeUndefined<Result<T, E>> {
 	/// Transposes a `MaybeUndefined` of a [`Result`] into a [`Result`] of a
 	/// `MaybeUndefined`.
@@ -86,3 +278,284 @@ where
 		})
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use proptest::prelude::*;
+	use serde::de::DeserializeOwned;
+
+	impl<T: Arbitrary + Clone + 'static> Arbitrary for MaybeUndefined<T> {
+		type Parameters = T::Parameters;
+		type Strategy = BoxedStrategy<Self>;
+
+		fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+			prop_oneof![
+				Just(Self::Undefined),
+				Just(Self::Null),
+				any_with::<T>(args).prop_map(Self::Value),
+			]
+			.boxed()
+		}
+	}
+
+	#[derive(Default, Deserialize, Type)]
+	struct Args {
+		#[serde(default)]
+		#[specta(optional)]
+		x: MaybeUndefined<i32>,
+	}
+
+	fn deserialize(json: &str) -> MaybeUndefined<i32> {
+		serde_json::from_str::<Args>(json).unwrap().x
+	}
+
+	#[test]
+	fn deserialize_missing_field_as_undefined() {
+		assert!(matches!(deserialize("{}"), MaybeUndefined::Undefined));
+	}
+
+	#[test]
+	fn deserialize_null_field_as_null() {
+		assert!(matches!(deserialize(r#"{"x":null}"#), MaybeUndefined::Null));
+	}
+
+	#[test]
+	fn deserialize_present_field_as_value() {
+		assert!(matches!(
+			deserialize(r#"{"x":5}"#),
+			MaybeUndefined::Value(5)
+		));
+	}
+
+	#[test]
+	fn compare_variants() {
+		assert_eq!(MaybeUndefined::Value(5), MaybeUndefined::Value(5));
+		assert_ne!(MaybeUndefined::Value(5), MaybeUndefined::Value(6));
+		assert_eq!(MaybeUndefined::<i32>::Null, MaybeUndefined::Null);
+		assert_ne!(MaybeUndefined::<i32>::Null, MaybeUndefined::Undefined);
+		assert_ne!(MaybeUndefined::Undefined, MaybeUndefined::Value(5));
+	}
+
+	#[test]
+	fn round_trip_option_option() {
+		for value in [
+			MaybeUndefined::Undefined,
+			MaybeUndefined::Null,
+			MaybeUndefined::Value(5),
+		] {
+			assert_eq!(
+				MaybeUndefined::from(value.clone().into_option_option()),
+				value
+			);
+		}
+	}
+
+	#[test]
+	fn filter_and_take() {
+		assert_eq!(
+			MaybeUndefined::Value(5).filter(|v| *v > 3),
+			MaybeUndefined::Value(5)
+		);
+		assert_eq!(
+			MaybeUndefined::Value(1).filter(|v| *v > 3),
+			MaybeUndefined::Undefined
+		);
+		assert_eq!(
+			MaybeUndefined::Null.filter(|v: &i32| *v > 3),
+			MaybeUndefined::Null
+		);
+
+		let mut value = MaybeUndefined::Value(5);
+		assert_eq!(value.take(), MaybeUndefined::Value(5));
+		assert_eq!(value, MaybeUndefined::Undefined);
+	}
+
+	#[test]
+	fn default_is_undefined() {
+		assert_eq!(
+			MaybeUndefined::<String>::default(),
+			MaybeUndefined::Undefined
+		);
+		assert_eq!(Args::default().x, MaybeUndefined::Undefined);
+	}
+
+	#[test]
+	fn export_typescript_type() {
+		let ts = Default::default();
+
+		assert_eq!(
+			specta_typescript::inline::<MaybeUndefined<String>>(&ts).unwrap(),
+			"string | null"
+		);
+		assert_eq!(
+			specta_typescript::inline::<Args>(&ts).unwrap(),
+			"{ x?: number | null }"
+		);
+	}
+
+	#[test]
+	fn ok_or_requires_defined() {
+		assert_eq!(
+			MaybeUndefined::<i32>::Undefined.ok_or("missing"),
+			Err("missing")
+		);
+		assert_eq!(MaybeUndefined::<i32>::Null.ok_or("missing"), Ok(None));
+		assert_eq!(
+			MaybeUndefined::Value(5).ok_or_else(|| "missing"),
+			Ok(Some(5))
+		);
+	}
+
+	#[test]
+	fn iterate_over_value() {
+		assert_eq!(MaybeUndefined::<i32>::Undefined.into_iter().next(), None);
+		assert_eq!(MaybeUndefined::<i32>::Null.into_iter().next(), None);
+		assert_eq!(MaybeUndefined::Value(5).iter().collect::<Vec<_>>(), [&5]);
+		assert_eq!(
+			MaybeUndefined::Value(5)
+				.into_iter()
+				.chain([6])
+				.collect::<Vec<_>>(),
+			[5, 6]
+		);
+	}
+
+	#[test]
+	fn zip_collapses_variants() {
+		use MaybeUndefined::{Null, Undefined, Value};
+
+		assert_eq!(Value(1).zip(Value("a")), Value((1, "a")));
+		assert_eq!(Value(1).zip(Undefined::<&str>), Undefined);
+		assert_eq!(Null::<i32>.zip(Undefined::<&str>), Undefined);
+		assert_eq!(Value(1).zip(Null::<&str>), Null);
+		assert_eq!(Null::<i32>.zip(Null::<&str>), Null);
+	}
+
+	#[test]
+	fn flatten_matrix() {
+		use MaybeUndefined::{Null, Undefined, Value};
+
+		for inner in [Undefined, Null, Value(5)] {
+			for (outer, expected) in [
+				(Undefined, Undefined),
+				(Null, Null),
+				(Value(inner.clone()), inner.clone()),
+			] {
+				assert_eq!(outer.flatten(), expected);
+			}
+		}
+	}
+
+	#[test]
+	fn unwrap_and_expect_values() {
+		assert_eq!(MaybeUndefined::Value(5).unwrap(), 5);
+		assert_eq!(MaybeUndefined::Value(5).expect("x should be set"), 5);
+	}
+
+	#[test]
+	#[should_panic(expected = "on a `Null` value")]
+	fn unwrap_null_panics() {
+		MaybeUndefined::<i32>::Null.unwrap();
+	}
+
+	#[test]
+	#[should_panic(expected = "x should be set")]
+	fn expect_undefined_panics() {
+		MaybeUndefined::<i32>::Undefined.expect("x should be set");
+	}
+
+	#[test]
+	fn to_update_skips_undefined() {
+		let set = |x| ("x", x);
+
+		assert_eq!(MaybeUndefined::<i32>::Undefined.to_update(set), None);
+		assert_eq!(
+			MaybeUndefined::<i32>::Null.to_update(set),
+			Some(("x", None))
+		);
+		assert_eq!(
+			MaybeUndefined::Value(5).to_update(set),
+			Some(("x", Some(5)))
+		);
+	}
+
+	#[test]
+	fn as_deref_borrows_target() {
+		assert_eq!(
+			MaybeUndefined::Value("name".to_string()).as_deref(),
+			MaybeUndefined::Value("name")
+		);
+		assert_eq!(
+			MaybeUndefined::Value(vec![1, 2]).as_deref(),
+			MaybeUndefined::Value([1, 2].as_slice())
+		);
+		assert_eq!(
+			MaybeUndefined::<String>::Null.as_deref(),
+			MaybeUndefined::Null
+		);
+		assert_eq!(
+			MaybeUndefined::<String>::Undefined.as_deref(),
+			MaybeUndefined::Undefined
+		);
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Field<T> {
+		#[serde(default, skip_serializing_if = "MaybeUndefined::is_undefined")]
+		x: MaybeUndefined<T>,
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct NullableField<T> {
+		#[serde(default)]
+		x: MaybeUndefined<T>,
+	}
+
+	fn round_trip<T: Serialize + DeserializeOwned>(x: MaybeUndefined<T>) -> MaybeUndefined<T> {
+		let json = serde_json::to_string(&Field { x }).unwrap();
+		serde_json::from_str::<Field<T>>(&json).unwrap().x
+	}
+
+	proptest! {
+		#[test]
+		fn round_trip_numbers(x in any::<MaybeUndefined<i64>>()) {
+			prop_assert_eq!(round_trip(x.clone()), x);
+		}
+
+		#[test]
+		fn round_trip_strings(x in any::<MaybeUndefined<String>>()) {
+			prop_assert_eq!(round_trip(x.clone()), x);
+		}
+
+		#[test]
+		fn round_trip_lists(x in any::<MaybeUndefined<Vec<u8>>>()) {
+			prop_assert_eq!(round_trip(x.clone()), x);
+		}
+
+		#[test]
+		fn round_trip_nested(x in any::<MaybeUndefined<MaybeUndefined<bool>>>()) {
+			// An inner `Undefined` or `Null` is serialized as `null` too
+			let expected = match x.clone() {
+				MaybeUndefined::Value(MaybeUndefined::Undefined | MaybeUndefined::Null) => {
+					MaybeUndefined::Null
+				}
+				other => other,
+			};
+
+			prop_assert_eq!(round_trip(x), expected);
+		}
+
+		#[test]
+		fn undefined_serialized_as_null(x in any::<MaybeUndefined<String>>()) {
+			let json = serde_json::to_string(&NullableField { x: x.clone() }).unwrap();
+			let expected = if x.is_undefined() { MaybeUndefined::Null } else { x };
+
+			prop_assert_eq!(
+				serde_json::from_str::<NullableField<String>>(&json).unwrap().x,
+				expected
+			);
+		}
+	}
+}
@@ -1,12 +1,14 @@
 use crate::{
 	primitives::{EncryptedBlock, EncryptedBlockRef, StreamNonce},
-	Error,
+	Error, Protected,
 };
 
 use std::future::Future;
 
 use aead::{stream::DecryptorLE31, Aead, KeyInit};
+use async_stream::try_stream;
 use chacha20poly1305::XChaCha20Poly1305;
+use futures::Stream;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
 
 use super::secret_key::SecretKey;
@@ -23,6 +25,14 @@ pub trait StreamDecryption {
 		reader: impl AsyncRead + Unpin + Send,
 		writer: impl AsyncWrite + Unpin + Send,
 	) -> impl Future<Output = Result<(), Error>> + Send;
+
+	/// Same as `.decrypt()`, but yielding each decrypted block instead of writing it out, so the
+	/// whole plain text is never held in memory at once. Each block is zeroized once dropped.
+	fn decrypt_blocks(
+		&self,
+		nonce: &StreamNonce,
+		reader: impl AsyncRead + Unpin + Send,
+	) -> impl Stream<Item = Result<Protected<Vec<u8>>, Error>> + Send;
 }
 
 impl OneShotDecryption for SecretKey {
@@ -112,4 +122,48 @@ impl StreamDecryption for SecretKey {
 
 		Ok(())
 	}
+	fn decrypt_blocks(
+		&self,
+		nonce: &StreamNonce,
+		reader: impl AsyncRead + Unpin + Send,
+	) -> impl Stream<Item = Result<Protected<Vec<u8>>, Error>> + Send {
+		try_stream! {
+			let mut reader = BufReader::with_capacity(EncryptedBlock::CIPHER_TEXT_SIZE, reader);
+			let mut decryptor = DecryptorLE31::from_aead(XChaCha20Poly1305::new(&self.0), nonce);
+
+			loop {
+				let bytes = reader.fill_buf().await.map_err(|e| Error::DecryptIo {
+					context: "Reading a block from the reader",
+					source: e,
+				})?;
+
+				if bytes.is_empty() {
+					// Jobs done
+					break;
+				}
+
+				let total_bytes = bytes.len();
+
+				// Decrypted in place, so the plain text never lives outside of a `Protected`
+				let mut block = Protected::new(bytes.to_vec());
+
+				reader.consume(total_bytes);
+
+				if total_bytes == EncryptedBlock::CIPHER_TEXT_SIZE {
+					decryptor
+						.decrypt_next_in_place(b"", block.expose_mut())
+						.map_err(|aead::Error| Error::Decrypt)?;
+
+					yield block;
+				} else {
+					decryptor
+						.decrypt_last_in_place(b"", block.expose_mut())
+						.map_err(|aead::Error| Error::Decrypt)?;
+
+					yield block;
+					break;
+				}
+			}
+		}
+	}
 }
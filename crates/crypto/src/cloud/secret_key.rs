@@ -1,7 +1,7 @@
 use crate::{
 	ct::{Choice, ConstantTimeEq, ConstantTimeEqNull},
 	rng::CryptoRng,
-	Error,
+	Error, Protected, ProtectedSlice,
 };
 
 use std::fmt;
@@ -151,6 +151,33 @@ impl From<GenericArray<u8, U64>> for SecretKey {
 	}
 }
 
+/// Protected keys can be turned into AEAD keys without exposing them, the key is moved out of
+/// the `Protected` value, leaving zeroes behind
+impl From<Protected<[u8; 32]>> for SecretKey {
+	fn from(key: Protected<[u8; 32]>) -> Self {
+		Self(Array(key.into_inner()))
+	}
+}
+
+impl TryFrom<ProtectedSlice<'_>> for SecretKey {
+	type Error = Error;
+
+	fn try_from(key: ProtectedSlice<'_>) -> Result<Self, Self::Error> {
+		Self::try_from(key.expose())
+	}
+}
+
+impl<T> TryFrom<&Protected<T>> for SecretKey
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	type Error = Error;
+
+	fn try_from(key: &Protected<T>) -> Result<Self, Self::Error> {
+		Self::try_from(key.as_slice())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use std::pin::pin;
@@ -162,6 +189,22 @@ mod tests {
 
 	use super::*;
 
+	#[test]
+	fn from_protected() {
+		let bytes = [42u8; 32];
+
+		let key = SecretKey::from(Protected::new(bytes));
+		assert_eq!(key.as_ref(), &bytes);
+
+		assert!(SecretKey::try_from(&Protected::new(bytes.to_vec())).is_ok_and(|k| k == key));
+		assert!(SecretKey::try_from(&Protected::new(Box::<[u8]>::from(bytes))).is_ok());
+		assert!(SecretKey::try_from(Protected::new(bytes).as_slice()).is_ok_and(|k| k == key));
+		assert!(matches!(
+			SecretKey::try_from(&Protected::new(vec![42u8; 16])),
+			Err(Error::InvalidKeySize(16))
+		));
+	}
+
 	#[test]
 	fn one_shot_test() {
 		use super::super::{decrypt::OneShotDecryption, encrypt::OneShotEncryption};
@@ -226,6 +269,17 @@ mod tests {
 			.unwrap();
 
 		assert_eq!(message, decrypted_message.as_slice());
+
+		let mut blocks = pin!(key.decrypt_blocks(&nonce, encrypted_message.as_slice()));
+		let mut decrypted_blocks = vec![];
+
+		while let Some(block) = blocks.next().await {
+			let block = block.unwrap();
+			assert!(block.expose().len() <= EncryptedBlock::PLAIN_TEXT_SIZE);
+			decrypted_blocks.extend_from_slice(block.expose());
+		}
+
+		assert_eq!(message, decrypted_blocks.as_slice());
 	}
 
 	#[tokio::test]
@@ -39,8 +39,20 @@ pub enum Error {
 		source: io::Error,
 	},
 
+	/// I/O error while writing or erasing a temporary file
+	#[error("I/O error on temporary file: {{context: {context}, source: {source}}}")]
+	TempFileIo {
+		context: &'static str,
+		#[source]
+		source: io::Error,
+	},
+
 	#[error("hex error: {0}")]
 	Hex(#[from] hex::FromHexError),
+	#[error("base64 error: {0}")]
+	Base64(#[from] base64::DecodeSliceError),
+	#[error("utf-8 error: {0}")]
+	Utf8(#[from] std::str::Utf8Error),
 
 	#[error("Entropy source error: {0}")]
 	EntropySource(#[from] rand_core::getrandom::Error),
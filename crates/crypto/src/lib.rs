@@ -35,10 +35,12 @@ pub mod error;
 pub mod primitives;
 pub mod protected;
 pub mod rng;
+pub mod temp_file;
 
 pub use error::Error;
-pub use protected::Protected;
+pub use protected::{Protected, ProtectedGuard, ProtectedLocked, ProtectedSlice, ProtectedString};
 pub use rng::CryptoRng;
+pub use temp_file::ProtectedTempFile;
 
 pub use rand_core::{RngCore, SeedableRng};
 
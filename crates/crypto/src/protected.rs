@@ -7,8 +7,13 @@
 //!
 //! `Protected` values are also hidden from `fmt::Debug`, and will display `[REDACTED]` instead.
 //!
+//! `Protected` values can only be serialized and deserialized with the `serialize-secrets` feature,
+//! so they can't be written out to a file or logs by accident.
+//!
 //! The only way to access the data within a `Protected` value is to call `.expose()` - this is to prevent accidental leakage.
 //! This also makes any `Protected` value easier to audit, as you are able to quickly view wherever the data is accessed.
+//! That's why `Protected` must never implement `Deref`, `AsRef` or `Borrow` into its value, which is checked by the tests.
+//! The value itself is sealed in a private type only `.expose()` can open, so not even the rest of this crate can read it directly.
 //!
 //! `Protected` values are not able to be copied within memory, to prevent accidental leakage. They are able to be `cloned` however - but this is always explicit and you will be aware of it.
 //!
@@ -29,14 +34,68 @@
 //! ```
 //!
 
-use std::{fmt::Debug, mem};
+use std::{
+	fmt::{self, Debug, Display},
+	mem,
+	slice::SliceIndex,
+};
 
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand_core::RngCore;
+#[cfg(feature = "serialize-secrets")]
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
-#[derive(Clone, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct Protected<T>(T)
+use crate::{
+	ct::{Choice, ConstantTimeEq},
+	CryptoRng, Error,
+};
+
+mod sealed {
+	#[cfg(feature = "serialize-secrets")]
+	use serde::{Deserialize, Serialize};
+	use zeroize::Zeroize;
+
+	/// The value of a `Protected`, its field is only visible in this module, so it can't be read
+	/// anywhere else but through `Protected::expose()` and `Protected::expose_mut()`.
+	#[derive(Clone, Zeroize)]
+	#[cfg_attr(
+		feature = "serialize-secrets",
+		derive(Serialize, Deserialize),
+		serde(transparent)
+	)]
+	pub struct Sealed<T>(T)
+	where
+		T: Zeroize;
+
+	impl<T> Sealed<T>
+	where
+		T: Zeroize,
+	{
+		pub(super) const fn new(value: T) -> Self {
+			Self(value)
+		}
+
+		pub(super) const fn get(&self) -> &T {
+			&self.0
+		}
+
+		pub(super) fn get_mut(&mut self) -> &mut T {
+			&mut self.0
+		}
+	}
+}
+
+use sealed::Sealed;
+
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+#[cfg_attr(
+	feature = "serialize-secrets",
+	derive(Serialize, Deserialize),
+	serde(transparent)
+)]
+pub struct Protected<T>(Sealed<T>)
 where
 	T: Zeroize;
 
@@ -45,21 +104,65 @@ where
 	T: Zeroize,
 {
 	pub const fn new(value: T) -> Self {
-		Self(value)
+		Self(Sealed::new(value))
 	}
 
+	#[must_use]
 	pub const fn expose(&self) -> &T {
-		&self.0
+		self.0.get()
+	}
+
+	/// Mutable counterpart of `.expose()`, with the same caveat: the data behind the reference
+	/// must not be copied out or leaked in any other way.
+	#[must_use]
+	pub fn expose_mut(&mut self) -> &mut T {
+		self.0.get_mut()
 	}
 
 	pub fn zeroize(mut self) {
-		self.0.zeroize();
+		self.expose_mut().zeroize();
+	}
+
+	/// Zeroizes the secret early, while keeping the wrapper around to assign a new value later.
+	///
+	/// The wrapper is left holding `T::default()` (e.g. an empty `Vec`), instead of being dropped.
+	pub fn zeroize_in_place(&mut self)
+	where
+		T: Default,
+	{
+		self.expose_mut().zeroize();
+		*self.expose_mut() = T::default();
+	}
+
+	/// A safe way to show a secret in a UI or logs, it always displays `[REDACTED]`.
+	#[must_use]
+	#[allow(clippy::unused_self)]
+	pub const fn redacted_display(&self) -> impl Display {
+		Redacted
+	}
+
+	/// Transforms the secret into another `Protected` value, zeroizing the original one.
+	///
+	/// `f` only borrows the secret, as a secret moved into it would be dropped without being
+	/// zeroized.
+	pub fn map<U: Zeroize>(self, f: impl FnOnce(&T) -> U) -> Protected<U> {
+		Protected::new(f(self.expose()))
+	}
+
+	/// Fallible version of `.map()`, the original secret is zeroized whether `f` fails or not.
+	///
+	/// Any partial state built by `f` is up to it, so it should be kept in a `Protected` value too.
+	pub fn try_map<U: Zeroize, E>(
+		self,
+		f: impl FnOnce(&T) -> Result<U, E>,
+	) -> Result<Protected<U>, E> {
+		f(self.expose()).map(Protected::new)
 	}
 }
 
 impl<T: Zeroize> From<T> for Protected<T> {
 	fn from(value: T) -> Self {
-		Self(value)
+		Self::new(value)
 	}
 }
 
@@ -69,16 +172,541 @@ where
 {
 	pub fn into_inner(mut self) -> T {
 		let mut out = Default::default();
-		mem::swap(&mut self.0, &mut out);
+		mem::swap(self.expose_mut(), &mut out);
+		out
+	}
+}
+
+/// A `Protected` passphrase or any other secret text, which can't be displayed or turned into a
+/// plain `String` without an explicit `.expose()`
+pub type ProtectedString = Protected<String>;
+
+impl ProtectedString {
+	/// Converts the bytes into a `String` without copying them, the bytes are zeroized if they
+	/// aren't valid UTF-8
+	pub fn from_utf8(bytes: Protected<Vec<u8>>) -> Result<Self, Error> {
+		String::from_utf8(bytes.into_inner())
+			.map(Self::new)
+			.map_err(|e| {
+				let error = e.utf8_error();
+				e.into_bytes().zeroize();
+				error.into()
+			})
+	}
+
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.expose().len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.expose().is_empty()
+	}
+}
+
+/// Encoding helpers, every intermediate buffer is itself `Protected`, so partially decoded or
+/// encoded data is zeroized as well, even on errors.
+impl Protected<Vec<u8>> {
+	/// Allocates a zeroed buffer of `len` bytes for `fill` to write into, it's protected from
+	/// the start, so there's never an unprotected copy of the key material.
+	pub fn new_filled(len: usize, fill: impl FnOnce(&mut [u8])) -> Self {
+		let mut out = Self::new(vec![0; len]);
+		fill(out.expose_mut());
 		out
 	}
+
+	/// Generates `len` random bytes, the safe way to generate key material
+	pub fn new_random(len: usize, rng: &mut CryptoRng) -> Self {
+		Self::new_filled(len, |buf| rng.fill_bytes(buf))
+	}
+
+	/// Exposes only a sub-range of the secret, e.g. the first 16 bytes of a seed to be used as an
+	/// IV, returning `None` if the range is out of bounds.
+	#[must_use]
+	pub fn expose_range(&self, range: impl SliceIndex<[u8], Output = [u8]>) -> Option<&[u8]> {
+		self.expose().get(range)
+	}
+
+	pub fn from_hex(input: &str) -> Result<Self, Error> {
+		let mut out = Self::new(vec![0; input.len() / 2]);
+		hex::decode_to_slice(input, out.expose_mut())?;
+		Ok(out)
+	}
+
+	pub fn from_base64(input: &str) -> Result<Self, Error> {
+		let mut out = Self::new(vec![0; base64::decoded_len_estimate(input.len())]);
+		let len = STANDARD.decode_slice(input, out.expose_mut())?;
+		out.expose_mut().truncate(len);
+		Ok(out)
+	}
+
+	#[must_use]
+	pub fn expose_hex(&self) -> Protected<String> {
+		let mut out = Self::new(vec![0; self.expose().len() * 2]);
+		// Can't fail, as the output is exactly twice as long as the input
+		let _ = hex::encode_to_slice(self.expose(), out.expose_mut());
+		out.map(|encoded| ascii_to_string(encoded))
+	}
+
+	#[must_use]
+	pub fn expose_base64(&self) -> Protected<String> {
+		let len = base64::encoded_len(self.expose().len(), true).unwrap_or_default();
+		let mut out = Self::new(vec![0; len]);
+		// Can't fail, as the output has exactly the encoded length
+		let _ = STANDARD.encode_slice(self.expose(), out.expose_mut());
+		out.map(|encoded| ascii_to_string(encoded))
+	}
+}
+
+/// Both hex and base64 are ASCII, so each byte is a single char and the `String` is allocated
+/// once, without leaving copies behind.
+fn ascii_to_string(bytes: &[u8]) -> String {
+	bytes.iter().copied().map(char::from).collect()
+}
+
+impl<T> Protected<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	/// Shorthand for `.expose().as_ref()`, to pass the secret to APIs taking bytes, like AEADs or
+	/// MACs. Named like `.expose()` so it shows up when auditing where secrets are accessed.
+	#[must_use]
+	pub fn expose_bytes(&self) -> &[u8] {
+		self.expose().as_ref()
+	}
+
+	/// Borrows the secret as a byte slice that stays protected, to pass any kind of buffer, like
+	/// a `Vec<u8>`, a `Box<[u8]>` or a `[u8; N]`, to APIs taking bytes without exposing it yet.
+	#[must_use]
+	pub fn as_slice(&self) -> ProtectedSlice<'_> {
+		ProtectedSlice(self.expose().as_ref())
+	}
+
+	/// Compares both secrets in constant-time, so the comparison doesn't leak how much of them
+	/// matched.
+	///
+	/// `Protected` doesn't implement `PartialEq`, as it would be too easy to compare secrets in
+	/// variable time.
+	#[must_use]
+	pub fn constant_time_eq(&self, other: &Self) -> bool {
+		self.ct_eq(other).into()
+	}
+}
+
+impl<T> ConstantTimeEq for Protected<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	fn ct_eq(&self, rhs: &Self) -> Choice {
+		self.expose_bytes().ct_eq(rhs.expose_bytes())
+	}
+}
+
+/// Equality is checked in constant-time, and `Hash` always goes over the whole array.
+#[cfg(feature = "constant-time-eq")]
+impl<const N: usize> PartialEq for Protected<[u8; N]> {
+	fn eq(&self, other: &Self) -> bool {
+		self.expose().ct_eq(other.expose()).into()
+	}
+}
+
+#[cfg(feature = "constant-time-eq")]
+impl<const N: usize> Eq for Protected<[u8; N]> {}
+
+#[cfg(feature = "constant-time-eq")]
+impl<const N: usize> std::hash::Hash for Protected<[u8; N]> {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.expose().hash(state);
+	}
 }
 
 impl<T> Debug for Protected<T>
 where
 	T: Zeroize,
 {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		Debug::fmt(&Redacted, f)
+	}
+}
+
+struct Redacted;
+
+impl Display for Redacted {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.write_str("[REDACTED]")
 	}
 }
+
+impl Debug for Redacted {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		Display::fmt(self, f)
+	}
+}
+
+/// A borrowed secret buffer, zeroized when the guard goes out of scope, like a decrypted chunk
+/// in a buffer that's reused for the next one. Avoids allocating a `Protected<Vec<u8>>` for
+/// transient secrets.
+///
+/// The owner of the buffer regains access to it once the guard is dropped, by then zeroized.
+pub struct ProtectedGuard<'a>(&'a mut [u8]);
+
+impl<'a> ProtectedGuard<'a> {
+	pub fn new(buf: &'a mut [u8]) -> Self {
+		Self(buf)
+	}
+
+	#[must_use]
+	pub fn expose(&self) -> &[u8] {
+		&*self.0
+	}
+
+	#[must_use]
+	pub fn expose_mut(&mut self) -> &mut [u8] {
+		&mut *self.0
+	}
+}
+
+impl Drop for ProtectedGuard<'_> {
+	fn drop(&mut self) {
+		self.0.zeroize();
+	}
+}
+
+impl ZeroizeOnDrop for ProtectedGuard<'_> {}
+
+impl Debug for ProtectedGuard<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		Debug::fmt(&Redacted, f)
+	}
+}
+
+/// A borrowed view of the bytes of a `Protected` value, from `.as_slice()`, which keeps them
+/// hidden behind `.expose()` just like its source. Nothing is zeroized on drop, as the bytes are
+/// still owned by the source.
+pub struct ProtectedSlice<'a>(&'a [u8]);
+
+impl<'a> ProtectedSlice<'a> {
+	#[must_use]
+	pub const fn expose(&self) -> &'a [u8] {
+		self.0
+	}
+
+	#[must_use]
+	pub const fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+impl Debug for ProtectedSlice<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		Debug::fmt(&Redacted, f)
+	}
+}
+
+/// A `Protected` byte buffer whose memory is locked (with `mlock`/`VirtualLock`), so the OS
+/// can't swap it out to disk while it's alive. Meant for key material that lives as long as
+/// a library.
+///
+/// If the memory can't be locked (unsupported platform, missing permissions or a too low
+/// `RLIMIT_MEMLOCK`) a warning is logged and the value is still zeroized on drop.
+///
+/// Locks are held per memory page, so dropping a value also unlocks any other value sharing
+/// one of its pages.
+pub struct ProtectedLocked<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	// Zeroized and unlocked on drop, before the box is freed
+	value: Box<Protected<T>>,
+	lock: Option<region::LockGuard>,
+}
+
+impl<T> ProtectedLocked<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	pub fn new(value: T) -> Self {
+		// Boxed before locking, so an inline value like `[u8; N]` is locked where it lives from
+		// now on, instead of in a stack copy left behind when moving it into `Self`. Heap buffers
+		// like `Vec<u8>` are only ever exposed immutably, so they aren't reallocated either
+		let value = Box::new(Protected::new(value));
+		let bytes = value.expose().as_ref();
+
+		let lock = if bytes.is_empty() {
+			None
+		} else {
+			region::lock(bytes.as_ptr(), bytes.len())
+				.inspect_err(|e| warn!(?e, "Failed to lock protected memory, it may be swapped;"))
+				.ok()
+		};
+
+		Self { value, lock }
+	}
+
+	#[must_use]
+	pub fn expose(&self) -> &T {
+		self.value.expose()
+	}
+
+	/// Whether the memory is actually locked, as locking may have failed
+	pub const fn is_locked(&self) -> bool {
+		self.lock.is_some()
+	}
+}
+
+impl<T> Drop for ProtectedLocked<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	fn drop(&mut self) {
+		// The fields are only dropped once this returns, so the memory is zeroized and unlocked
+		// while it's still allocated, instead of unlocking pages the allocator may have reused
+		self.value.expose_mut().zeroize();
+		self.lock.take();
+	}
+}
+
+impl<T> From<T> for ProtectedLocked<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	fn from(value: T) -> Self {
+		Self::new(value)
+	}
+}
+
+impl<T> Debug for ProtectedLocked<T>
+where
+	T: Zeroize + AsRef<[u8]>,
+{
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		Debug::fmt(&Redacted, f)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{cell::Cell, rc::Rc};
+
+	use zeroize::Zeroize;
+
+	use crate::CryptoRng;
+
+	use super::{Protected, ProtectedGuard, ProtectedLocked, ProtectedSlice, ProtectedString};
+
+	// `.expose()` must stay the only way to read a secret
+	static_assertions::assert_not_impl_any!(
+		Protected<Vec<u8>>: std::ops::Deref,
+		std::ops::DerefMut,
+		AsRef<Vec<u8>>,
+		AsRef<[u8]>,
+		AsMut<Vec<u8>>,
+		std::borrow::Borrow<Vec<u8>>,
+		std::borrow::Borrow<[u8]>,
+	);
+
+	static_assertions::assert_not_impl_any!(
+		ProtectedSlice<'static>: std::ops::Deref,
+		AsRef<[u8]>,
+		std::borrow::Borrow<[u8]>,
+	);
+
+	// Held by libraries shared between threads
+	static_assertions::assert_impl_all!(ProtectedLocked<Vec<u8>>: Send, Sync);
+
+	struct Secret(Rc<Cell<bool>>);
+
+	impl Zeroize for Secret {
+		fn zeroize(&mut self) {
+			self.0.set(true);
+		}
+	}
+
+	#[test]
+	fn constant_time_eq() {
+		let secret = Protected::new(b"secret".to_vec());
+
+		assert!(secret.constant_time_eq(&Protected::new(b"secret".to_vec())));
+		assert!(!secret.constant_time_eq(&Protected::new(b"Secret".to_vec())));
+		assert!(!secret.constant_time_eq(&Protected::new(b"secrets".to_vec())));
+		assert!(Protected::new("secret".to_string())
+			.constant_time_eq(&Protected::new("secret".to_string())));
+	}
+
+	#[test]
+	fn locked_exposes_value() {
+		let secret = ProtectedLocked::new(vec![42u8; 64]);
+
+		assert_eq!(secret.expose(), &vec![42u8; 64]);
+		assert!(!ProtectedLocked::new(Vec::<u8>::new()).is_locked());
+	}
+
+	#[test]
+	fn locked_inline_value_stays_in_place() {
+		let secret = ProtectedLocked::new([42u8; 32]);
+		let locked_at = secret.expose().as_ptr();
+
+		// Moving the wrapper must not move the locked bytes
+		let moved = Some(secret);
+
+		assert_eq!(moved.as_ref().unwrap().expose().as_ptr(), locked_at);
+		assert_eq!(moved.unwrap().expose(), &[42u8; 32]);
+	}
+
+	#[test]
+	fn map_zeroizes_source() {
+		let zeroized = Rc::new(Cell::new(false));
+
+		let mapped = Protected::new(Secret(Rc::clone(&zeroized))).map(|_| vec![1u8, 2, 3]);
+
+		assert!(zeroized.get());
+		assert_eq!(mapped.expose(), &[1, 2, 3]);
+	}
+
+	#[test]
+	fn hex_round_trip() {
+		let secret = Protected::from_hex("00ff10").unwrap();
+
+		assert_eq!(secret.expose(), &[0x00, 0xff, 0x10]);
+		assert_eq!(secret.expose_hex().expose(), "00ff10");
+		assert!(Protected::from_hex("00f").is_err());
+		assert!(Protected::from_hex("zz").is_err());
+	}
+
+	#[test]
+	fn base64_round_trip() {
+		let secret = Protected::from_base64("c2VjcmV0").unwrap();
+
+		assert_eq!(secret.expose(), b"secret");
+		assert_eq!(secret.expose_base64().expose(), "c2VjcmV0");
+		assert_eq!(
+			Protected::new(b"secrets".to_vec()).expose_base64().expose(),
+			"c2VjcmV0cw=="
+		);
+		assert!(Protected::from_base64("c2Vj*mV0").is_err());
+	}
+
+	#[test]
+	#[cfg(feature = "constant-time-eq")]
+	fn fixed_size_keys_as_map_keys() {
+		use std::collections::HashSet;
+
+		let keys = HashSet::from([Protected::new([1u8; 32]), Protected::new([2u8; 32])]);
+
+		assert!(keys.contains(&Protected::new([1u8; 32])));
+		assert!(!keys.contains(&Protected::new([3u8; 32])));
+		assert_eq!(Protected::new([1u8; 32]), Protected::new([1u8; 32]));
+	}
+
+	#[test]
+	fn string_from_utf8() {
+		let passphrase =
+			ProtectedString::from_utf8(Protected::new(b"passphrase".to_vec())).unwrap();
+
+		assert_eq!(passphrase.expose(), "passphrase");
+		assert_eq!(passphrase.len(), 10);
+		assert!(!passphrase.is_empty());
+		assert!(ProtectedString::from_utf8(Protected::new(vec![0xff, 0xfe])).is_err());
+	}
+
+	#[test]
+	fn try_map_zeroizes_source_on_error() {
+		let zeroized = Rc::new(Cell::new(false));
+
+		let mapped =
+			Protected::new(Secret(Rc::clone(&zeroized))).try_map(|_| Err::<Vec<u8>, _>(()));
+
+		assert!(zeroized.get());
+		assert!(mapped.is_err());
+	}
+
+	#[test]
+	fn redacted() {
+		let secret = Protected::new("secret".to_string());
+
+		assert_eq!(secret.redacted_display().to_string(), "[REDACTED]");
+		assert_eq!(format!("{secret:?}"), "[REDACTED]");
+	}
+
+	#[test]
+	fn zeroize_in_place_keeps_wrapper() {
+		let mut key = Protected::new(vec![42u8; 32]);
+
+		key.zeroize_in_place();
+		assert!(key.expose().is_empty());
+
+		*key.expose_mut() = vec![7u8; 32];
+		assert_eq!(key.expose(), &[7u8; 32]);
+	}
+
+	#[test]
+	fn new_filled_and_random() {
+		let filled = Protected::new_filled(4, |buf| buf.copy_from_slice(&[1, 2, 3, 4]));
+		assert_eq!(filled.expose(), &[1, 2, 3, 4]);
+
+		let mut rng = CryptoRng::new().unwrap();
+		let random = Protected::new_random(32, &mut rng);
+		assert_eq!(random.expose().len(), 32);
+		assert!(!random.constant_time_eq(&Protected::new_random(32, &mut rng)));
+	}
+
+	#[test]
+	fn expose_range() {
+		let seed = Protected::new((0..64).collect::<Vec<u8>>());
+
+		assert_eq!(
+			seed.expose_range(..16),
+			Some((0..16).collect::<Vec<_>>().as_slice())
+		);
+		assert_eq!(seed.expose_range(60..), Some([60, 61, 62, 63].as_slice()));
+		assert_eq!(seed.expose_range(32..80), None);
+	}
+
+	#[test]
+	fn guard_zeroizes_borrowed_buffer() {
+		let mut buf = [42u8; 16];
+
+		{
+			let mut guard = ProtectedGuard::new(&mut buf);
+			assert_eq!(guard.expose(), &[42u8; 16]);
+
+			guard.expose_mut()[0] = 7;
+			assert_eq!(guard.expose()[0], 7);
+			assert_eq!(format!("{guard:?}"), "[REDACTED]");
+		}
+
+		assert_eq!(buf, [0u8; 16]);
+	}
+
+	#[test]
+	fn expose_bytes() {
+		assert_eq!(Protected::new(b"secret".to_vec()).expose_bytes(), b"secret");
+		assert_eq!(Protected::new([1u8; 4]).expose_bytes(), &[1, 1, 1, 1]);
+		assert_eq!(
+			Protected::new("secret".to_string()).expose_bytes(),
+			b"secret"
+		);
+	}
+
+	#[test]
+	fn as_slice() {
+		let slice = [1u8, 2, 3, 4];
+
+		for secret in [
+			Protected::new(slice.to_vec()).as_slice(),
+			Protected::new(Box::<[u8]>::from(slice)).as_slice(),
+			Protected::new(slice).as_slice(),
+		] {
+			assert_eq!(secret.expose(), &slice);
+			assert_eq!(secret.len(), 4);
+			assert!(!secret.is_empty());
+			assert_eq!(format!("{secret:?}"), "[REDACTED]");
+		}
+	}
+}
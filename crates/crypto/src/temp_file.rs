@@ -0,0 +1,217 @@
+//! Temporary files holding decrypted data, for the tools that can only read from a path, like
+//! when generating the thumbnail of encrypted media.
+
+use crate::{erase::erase_sync, CryptoRng, Error, Protected};
+
+use std::{
+	fs::{self, OpenOptions},
+	io::Write,
+	path::{Path, PathBuf},
+};
+
+use rand_core::RngCore;
+use tracing::warn;
+use zeroize::Zeroize;
+
+/// Hints Windows to keep the file in its cache, instead of writing it to disk
+#[cfg(windows)]
+const FILE_ATTRIBUTE_TEMPORARY: u32 = 0x100;
+
+/// A temporary file holding `Protected` contents, only accessible by the current user: it's
+/// created with `0600` permissions on Unix, while on Windows it gets the ACL of its directory, so
+/// it can only be created in the temporary directory of the user. Its handle is never inherited by
+/// child processes.
+///
+/// On drop, the contents are overwritten with random data before the file is removed. This is
+/// best-effort, as flash storage may keep copies around (see [`erase_sync`]), and failures are
+/// only logged, so dropping never panics.
+#[derive(Debug)]
+pub struct ProtectedTempFile {
+	path: PathBuf,
+	len: usize,
+}
+
+impl ProtectedTempFile {
+	/// Creates a file in `dir` holding `contents`, leaving nothing behind on failure, like when
+	/// `dir` isn't writable.
+	///
+	/// On Windows, fails if `dir` isn't in the temporary directory of the user, see
+	/// [`ProtectedTempFile::in_temp_dir`].
+	///
+	/// This blocks while writing, so async code should call it from a blocking task.
+	pub fn new<T>(dir: impl AsRef<Path>, contents: &Protected<T>) -> Result<Self, Error>
+	where
+		T: Zeroize + AsRef<[u8]>,
+	{
+		#[cfg(windows)]
+		ensure_private_dir(dir.as_ref())?;
+
+		let mut name = [0u8; 16];
+		CryptoRng::new()?.fill_bytes(&mut name);
+
+		let path = dir.as_ref().join(format!(".sd-{}.tmp", hex::encode(name)));
+
+		let mut options = OpenOptions::new();
+
+		// Never reusing an existing file or following a symlink, as others may be able to read it
+		options.write(true).create_new(true);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::OpenOptionsExt;
+			options.mode(0o600);
+		}
+
+		#[cfg(windows)]
+		{
+			use std::os::windows::fs::OpenOptionsExt;
+			options.attributes(FILE_ATTRIBUTE_TEMPORARY);
+		}
+
+		let mut file = options.open(&path).map_err(|e| Error::TempFileIo {
+			context: "Creating temporary file",
+			source: e,
+		})?;
+
+		let contents = contents.expose_bytes();
+
+		// The file is erased on drop from now on, even if the write fails
+		let temp_file = Self {
+			path,
+			len: contents.len(),
+		};
+
+		file.write_all(contents)
+			.and_then(|()| file.sync_data())
+			.map_err(|e| Error::TempFileIo {
+				context: "Writing temporary file",
+				source: e,
+			})?;
+
+		Ok(temp_file)
+	}
+
+	/// Same as [`ProtectedTempFile::new`], in the temporary directory of the OS.
+	///
+	/// On Windows, files can only be created in this directory, or in directories inside it.
+	pub fn in_temp_dir<T>(contents: &Protected<T>) -> Result<Self, Error>
+	where
+		T: Zeroize + AsRef<[u8]>,
+	{
+		Self::new(std::env::temp_dir(), contents)
+	}
+
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	fn erase(&self) -> Result<(), Error> {
+		let erase_error = |context| move |source| Error::TempFileIo { context, source };
+
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(&self.path)
+			.map_err(erase_error("Opening temporary file to erase it"))?;
+
+		erase_sync(&mut file, self.len, 1)?;
+
+		file.sync_data()
+			.map_err(erase_error("Syncing erased temporary file"))?;
+		file.set_len(0)
+			.map_err(erase_error("Truncating erased temporary file"))
+	}
+}
+
+/// Windows files get the ACL of their directory, and giving them an owner-only one instead needs
+/// the Win32 security APIs, which are unsafe code this crate forbids. So temporary files are only
+/// created in the temporary directory of the user, which by default only they can access, instead
+/// of anywhere their contents could be read by others.
+#[cfg(windows)]
+fn ensure_private_dir(dir: &Path) -> Result<(), Error> {
+	let canonicalize = |path: &Path| {
+		fs::canonicalize(path).map_err(|e| Error::TempFileIo {
+			context: "Resolving temporary file directory",
+			source: e,
+		})
+	};
+
+	if canonicalize(dir)?.starts_with(canonicalize(&std::env::temp_dir())?) {
+		Ok(())
+	} else {
+		Err(Error::TempFileIo {
+			context: "Checking temporary file directory",
+			source: std::io::Error::new(
+				std::io::ErrorKind::PermissionDenied,
+				"temporary files must be in the temporary directory of the user on Windows",
+			),
+		})
+	}
+}
+
+impl Drop for ProtectedTempFile {
+	fn drop(&mut self) {
+		if let Err(e) = self.erase() {
+			warn!(path = %self.path.display(), ?e, "Failed to erase temporary file;");
+		}
+
+		if let Err(e) = fs::remove_file(&self.path) {
+			warn!(path = %self.path.display(), ?e, "Failed to remove temporary file;");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::Protected;
+
+	use super::ProtectedTempFile;
+
+	#[test]
+	fn removed_on_drop() {
+		let dir = tempfile::tempdir().unwrap();
+
+		let temp_file =
+			ProtectedTempFile::new(dir.path(), &Protected::new(b"plain text".to_vec())).unwrap();
+		let path = temp_file.path().to_path_buf();
+
+		assert_eq!(std::fs::read(&path).unwrap(), b"plain text");
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+
+			let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+			assert_eq!(mode & 0o777, 0o600);
+		}
+
+		drop(temp_file);
+
+		assert!(!path.exists());
+	}
+
+	#[test]
+	fn missing_dir() {
+		let dir = tempfile::tempdir().unwrap();
+
+		assert!(ProtectedTempFile::new(
+			dir.path().join("missing"),
+			&Protected::new(b"plain text".to_vec())
+		)
+		.is_err());
+	}
+
+	#[test]
+	#[cfg(windows)]
+	fn outside_temp_dir() {
+		let dir = std::env::current_dir().unwrap();
+
+		assert!(ProtectedTempFile::new(&dir, &Protected::new(b"plain text".to_vec())).is_err());
+		assert!(!std::fs::read_dir(&dir).unwrap().any(|entry| entry
+			.unwrap()
+			.file_name()
+			.to_string_lossy()
+			.starts_with(".sd-")));
+	}
+}
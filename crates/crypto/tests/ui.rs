@@ -0,0 +1,9 @@
+//! Compile-fail tests, checking that a `Protected` value can't be read without `.expose()`.
+//!
+//! The expected errors are in `tests/ui/*.stderr`, regenerate them with `TRYBUILD=overwrite`
+//! after a compiler upgrade changes their wording.
+
+#[test]
+fn protected_is_only_read_through_expose() {
+	trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
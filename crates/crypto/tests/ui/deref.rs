@@ -0,0 +1,7 @@
+use sd_crypto::Protected;
+
+fn main() {
+	let secret = Protected::new(vec![42u8; 32]);
+
+	let _: &Vec<u8> = &*secret;
+}
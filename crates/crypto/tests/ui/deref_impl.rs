@@ -0,0 +1,13 @@
+use std::ops::Deref;
+
+use sd_crypto::Protected;
+
+impl Deref for Protected<Vec<u8>> {
+	type Target = Vec<u8>;
+
+	fn deref(&self) -> &Self::Target {
+		self.expose()
+	}
+}
+
+fn main() {}
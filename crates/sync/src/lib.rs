@@ -31,6 +31,7 @@ mod compressed;
 mod crdt;
 mod factory;
 mod model_traits;
+pub mod pagination;
 
 pub use compressed::*;
 pub use crdt::*;
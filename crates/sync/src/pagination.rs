@@ -0,0 +1,232 @@
+//! Cursor based pagination, to go through large tables without loading them whole.
+//!
+//! Rows are fetched in pages ordered by ascending id, each page starting after the last id of the
+//! previous one, so unlike offset pagination it stays fast on large tables and doesn't skip rows
+//! when earlier rows are deleted in the meantime.
+
+use std::{error, fmt, future::Future};
+
+use async_stream::try_stream;
+use futures::Stream;
+
+/// Error of a paginated table walk.
+#[derive(Debug)]
+pub enum PaginationError<E, C> {
+	/// The getter failed to fetch a page.
+	Getter(E),
+	/// The last row of a page wasn't after the cursor, as happens with a getter not filtering and
+	/// ordering by ascending id, which would otherwise fetch the same page forever.
+	NonMonotonicCursor { previous: C, next: C },
+}
+
+impl<E: fmt::Display, C: fmt::Debug> fmt::Display for PaginationError<E, C> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Getter(e) => e.fmt(f),
+			Self::NonMonotonicCursor { previous, next } => write!(
+				f,
+				"pagination cursor didn't move forward, from {previous:?} to {next:?}"
+			),
+		}
+	}
+}
+
+impl<E: error::Error + 'static, C: fmt::Debug> error::Error for PaginationError<E, C> {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Self::Getter(e) => Some(e),
+			Self::NonMonotonicCursor { .. } => None,
+		}
+	}
+}
+
+/// Fetches pages of rows until an empty one, starting after the `start` id.
+///
+/// `getter` receives the cursor and must return the next page of rows with an id greater than
+/// it, ordered by ascending id, like:
+///
+/// ```ignore
+/// paginate(-1, |cursor| {
+/// 	db.tag()
+/// 		.find_many(vec![tag::id::gt(cursor)])
+/// 		.order_by(tag::id::order(SortOrder::Asc))
+/// 		.take(1000)
+/// 		.exec()
+/// }, |tag| tag.id)
+/// ```
+///
+/// Fails with [`PaginationError::NonMonotonicCursor`] if a page doesn't move the cursor forward.
+pub fn paginate<'a, T, E, GetterFut>(
+	start: i32,
+	getter: impl Fn(i32) -> GetterFut + Send + 'a,
+	id: impl Fn(&T) -> i32 + Send + 'a,
+) -> impl Stream<Item = Result<Vec<T>, PaginationError<E, i32>>> + Send + 'a
+where
+	T: Send + 'a,
+	E: Send + 'a,
+	GetterFut: Future<Output = Result<Vec<T>, E>> + Send + 'a,
+{
+	paginate_by_cursor(start, getter, id)
+}
+
+/// Same as [`paginate`], but for relation tables keyed by a group and an item id, like tags on
+/// objects, starting after the `start` pair of ids.
+///
+/// `getter` receives the group and item cursors, and must return the next page of rows after
+/// them ordered by ascending group id and then item id, like:
+///
+/// ```ignore
+/// paginate_relation((-1, -1), |group_id, item_id| {
+/// 	db.tag_on_object()
+/// 		.find_many(vec![or![
+/// 			tag_on_object::tag_id::gt(group_id),
+/// 			and![
+/// 				tag_on_object::tag_id::equals(group_id),
+/// 				tag_on_object::object_id::gt(item_id)
+/// 			]
+/// 		]])
+/// 		.order_by(tag_on_object::tag_id::order(SortOrder::Asc))
+/// 		.order_by(tag_on_object::object_id::order(SortOrder::Asc))
+/// 		.take(1000)
+/// 		.exec()
+/// }, |t_o| (t_o.tag_id, t_o.object_id))
+/// ```
+pub fn paginate_relation<'a, T, E, GetterFut>(
+	start: (i32, i32),
+	getter: impl Fn(i32, i32) -> GetterFut + Send + 'a,
+	id: impl Fn(&T) -> (i32, i32) + Send + 'a,
+) -> impl Stream<Item = Result<Vec<T>, PaginationError<E, (i32, i32)>>> + Send + 'a
+where
+	T: Send + 'a,
+	E: Send + 'a,
+	GetterFut: Future<Output = Result<Vec<T>, E>> + Send + 'a,
+{
+	paginate_by_cursor(
+		start,
+		move |(group_id, item_id)| getter(group_id, item_id),
+		id,
+	)
+}
+
+fn paginate_by_cursor<'a, T, E, C, GetterFut>(
+	start: C,
+	getter: impl Fn(C) -> GetterFut + Send + 'a,
+	id: impl Fn(&T) -> C + Send + 'a,
+) -> impl Stream<Item = Result<Vec<T>, PaginationError<E, C>>> + Send + 'a
+where
+	T: Send + 'a,
+	E: Send + 'a,
+	C: PartialOrd + Copy + Send + 'a,
+	GetterFut: Future<Output = Result<Vec<T>, E>> + Send + 'a,
+{
+	try_stream! {
+		let mut cursor = start;
+
+		loop {
+			let items = getter(cursor).await.map_err(PaginationError::Getter)?;
+
+			let Some(last) = items.last() else {
+				break;
+			};
+
+			let next = id(last);
+			if next <= cursor {
+				Err(PaginationError::NonMonotonicCursor {
+					previous: cursor,
+					next,
+				})?;
+			}
+
+			cursor = next;
+
+			yield items;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use futures::{executor::block_on, future, TryStreamExt};
+
+	const ROWS: [i32; 5] = [1, 2, 4, 7, 8];
+
+	/// Pages of up to two rows after the cursor, like a table ordered by id.
+	fn rows_after(cursor: i32) -> future::Ready<Result<Vec<i32>, ()>> {
+		future::ready(Ok(ROWS
+			.into_iter()
+			.filter(|row| *row > cursor)
+			.take(2)
+			.collect()))
+	}
+
+	#[test]
+	fn pages_through_every_row() {
+		let pages = block_on(paginate(-1, rows_after, |row| *row).try_collect::<Vec<_>>()).unwrap();
+
+		assert_eq!(pages, [vec![1, 2], vec![4, 7], vec![8]]);
+	}
+
+	#[test]
+	fn resumes_after_start() {
+		let pages = block_on(paginate(2, rows_after, |row| *row).try_collect::<Vec<_>>()).unwrap();
+
+		assert_eq!(pages, [vec![4, 7], vec![8]]);
+	}
+
+	#[test]
+	fn fails_on_non_monotonic_cursor() {
+		// Ignores the cursor, so it would fetch the first page forever
+		let res = block_on(paginate(-1, |_| rows_after(-1), |row| *row).try_collect::<Vec<_>>());
+
+		assert!(matches!(
+			res,
+			Err(PaginationError::NonMonotonicCursor {
+				previous: 2,
+				next: 2
+			})
+		));
+	}
+
+	#[test]
+	fn relation_compares_group_then_item() {
+		let rows = [(1, 5), (1, 6), (2, 1), (3, 0)];
+
+		let pages = block_on(
+			paginate_relation(
+				(1, 5),
+				|group_id, item_id| {
+					future::ready(Ok::<_, ()>(
+						rows.into_iter()
+							.filter(|row| *row > (group_id, item_id))
+							.take(2)
+							.collect::<Vec<_>>(),
+					))
+				},
+				|row| *row,
+			)
+			.try_collect::<Vec<_>>(),
+		)
+		.unwrap();
+
+		assert_eq!(pages, [vec![(1, 6), (2, 1)], vec![(3, 0)]]);
+
+		let res = block_on(
+			paginate_relation(
+				(-1, -1),
+				|_, _| future::ready(Ok::<_, ()>(vec![(2, 1), (1, 9)])),
+				|row| *row,
+			)
+			.try_collect::<Vec<_>>(),
+		);
+
+		assert!(matches!(
+			res,
+			Err(PaginationError::NonMonotonicCursor {
+				previous: (1, 9),
+				next: (1, 9)
+			})
+		));
+	}
+}
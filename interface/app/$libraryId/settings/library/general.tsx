@@ -1,10 +1,4 @@
-import {
-	MaybeUndefined,
-	useBridgeMutation,
-	useLibraryContext,
-	useLibraryMutation,
-	useZodForm
-} from '@sd/client';
+import { useBridgeMutation, useLibraryContext, useLibraryMutation, useZodForm } from '@sd/client';
 import { Button, dialogManager, Form, InputField, Switch, Tooltip, z } from '@sd/ui';
 import { useDebouncedFormWatch, useLocale } from '~/hooks';
 
@@ -18,11 +12,6 @@ const schema = z.object({
 	description: z.string().nullable()
 });
 
-// TODO: With some extra upstream Specta work this should be able to be removed
-function toMaybeUndefined<T>(v: T | null | undefined): MaybeUndefined<T> {
-	return v as any;
-}
-
 export const Component = () => {
 	const { library } = useLibraryContext();
 	const editLibrary = useBridgeMutation('library.edit');
@@ -45,7 +34,7 @@ export const Component = () => {
 		editLibrary.mutate({
 			id: library.uuid,
 			name: value.name ?? null,
-			description: toMaybeUndefined(value.description)
+			description: value.description
 		});
 	});
 
@@ -319,7 +319,7 @@ export type DiskType =
 
 export type DoubleClickAction = "openFile" | "quickPreview"
 
-export type EditLibraryArgs = { id: string; name: LibraryName | null; description: MaybeUndefined<string> }
+export type EditLibraryArgs = { id: string; name: LibraryName | null; description?: string | null }
 
 export type EphemeralFileCreateContextTypes = "empty" | "text"
 
@@ -574,8 +574,6 @@ export type LocationUpdateArgs = { id: number; name: string | null; generate_pre
 
 export type LocationWithIndexerRule = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; date_created: string | null; instance_id: number | null; indexer_rules: IndexerRule[] }
 
-export type MaybeUndefined<T> = null | T
-
 export type MediaData = { Exif: ExifMetadata } | { FFmpeg: FFmpegMetadata }
 
 /**